@@ -1,26 +1,301 @@
 //! loveletters commandline interface.
 
-use clap::Parser;
-use loveletters_lib::{error::Result, render_dir};
-use std::path::PathBuf;
+use clap::{Args as ClapArgs, Parser, Subcommand};
+use loveletters_lib::{
+    clear_package_cache, error::Result, find_project, list_cached_packages, list_fonts,
+    options::Options, prune_package_cache, render_dir, vendor,
+};
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    result,
+};
+use time::{
+    Date, OffsetDateTime, PrimitiveDateTime, Time,
+    format_description::well_known::{Iso8601, Rfc3339},
+};
 
 #[derive(Parser)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Make `loveletters` increasingly chatty.
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbosity: u8,
 
-    /// Directory to read content from.
-    input_dir: PathBuf,
+    /// Render all pages, bypassing the build cache.
+    #[arg(long)]
+    force: bool,
+
+    /// Print how long each phase took for each page, slowest page first.
+    #[arg(long)]
+    timings: bool,
+
+    /// Write a JSON report with timings, page counts, output sizes and cache hits to this file.
+    #[arg(long, value_name = "FILE")]
+    timings_json: Option<PathBuf>,
+
+    /// Only render pages whose content path (e.g. `content/posts/*`) or URL path (e.g.
+    /// `/posts/**`) matches this glob, leaving the remaining output untouched.
+    ///
+    /// May be given multiple times.
+    #[arg(long, value_name = "GLOB")]
+    only: Vec<String>,
+
+    #[command(flatten)]
+    build: BuildArgs,
+
+    /// Project directory (or any of its subdirectories) to read content from.
+    ///
+    /// Defaults to the project containing the current working directory.
+    input_dir: Option<PathBuf>,
 
     /// Directory to render output to.
-    output_dir: PathBuf,
+    ///
+    /// Defaults to the output directory configured in the project's `loveletters.toml`.
+    output_dir: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the fonts available to typst when rendering a project.
+    Fonts {
+        #[command(flatten)]
+        fonts: FontArgs,
+
+        /// Project directory (or any of its subdirectories).
+        ///
+        /// Defaults to the project containing the current working directory.
+        input_dir: Option<PathBuf>,
+    },
+    /// Render a project and copy all packages it uses into its `packages/` directory.
+    ///
+    /// Vendored packages take precedence over the package cache and downloads, so that a vendored
+    /// project builds without network access.
+    Vendor {
+        #[command(flatten)]
+        build: BuildArgs,
+
+        /// Project directory (or any of its subdirectories).
+        ///
+        /// Defaults to the project containing the current working directory.
+        input_dir: Option<PathBuf>,
+    },
+    /// Manage the package cache, which is shared with the typst CLI.
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// List the cached packages used by `loveletters` with their sizes and last use.
+    List,
+    /// Remove cached packages not recorded in any of the given projects' lockfiles.
+    Prune {
+        /// Project directories (or any of their subdirectories) whose packages to keep.
+        ///
+        /// Defaults to the project containing the current working directory.
+        input_dirs: Vec<PathBuf>,
+    },
+    /// Remove all cached packages used by `loveletters`.
+    Clear,
+}
+
+#[derive(ClapArgs)]
+struct BuildArgs {
+    /// Fail on unknown keys in the project configuration and frontmatter instead of warning about
+    /// them.
+    #[arg(long)]
+    strict: bool,
+
+    /// Add a string key-value pair visible to typst through `sys.inputs`.
+    ///
+    /// Takes precedence over inputs configured in the project's `loveletters.toml`.
+    #[arg(long = "input", value_name = "key=value", value_parser = parse_input)]
+    inputs: Vec<(String, String)>,
+
+    /// Number of pages to render concurrently.
+    ///
+    /// Defaults to the number of available CPUs.
+    #[arg(short, long)]
+    jobs: Option<NonZeroUsize>,
+
+    /// Never download packages, failing on packages not available locally instead.
+    #[arg(long)]
+    offline: bool,
+
+    /// Record new or changed package checksums in `loveletters.lock` instead of failing on them.
+    #[arg(long)]
+    update_lock: bool,
+
+    #[command(flatten)]
+    fonts: FontArgs,
+
+    /// Date (e.g. `2024-05-01`) or RFC 3339 timestamp of the build, as seen by `datetime.today()`.
+    ///
+    /// Takes precedence over the `SOURCE_DATE_EPOCH` environment variable. Defaults to the
+    /// current time.
+    #[arg(long, value_parser = parse_build_date)]
+    build_date: Option<OffsetDateTime>,
+}
+
+impl From<BuildArgs> for Options {
+    fn from(args: BuildArgs) -> Self {
+        Self {
+            strict: args.strict,
+            inputs: args.inputs,
+            jobs: args.jobs,
+            font_paths: args.fonts.font_paths,
+            ignore_system_fonts: args.fonts.ignore_system_fonts,
+            build_date: args.build_date,
+            offline: args.offline,
+            update_lock: args.update_lock,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(ClapArgs)]
+struct FontArgs {
+    /// Add a directory to search for fonts, in addition to the directories configured in the
+    /// project's `loveletters.toml`.
+    #[arg(long = "font-path", value_name = "DIR")]
+    font_paths: Vec<PathBuf>,
+
+    /// Do not make fonts installed on the system available.
+    #[arg(long)]
+    ignore_system_fonts: bool,
+}
+
+/// Parse a `key=value` pair.
+fn parse_input(raw: &str) -> result::Result<(String, String), String> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or_else(|| "input must be a key and a value separated by an equal sign".to_owned())?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("input key must not be empty".to_owned());
+    }
+    Ok((key.to_owned(), value.to_owned()))
+}
+
+/// Parse a date (at midnight UTC) or an RFC 3339 timestamp.
+fn parse_build_date(raw: &str) -> result::Result<OffsetDateTime, String> {
+    OffsetDateTime::parse(raw, &Rfc3339)
+        .or_else(|_| {
+            Date::parse(raw, &Iso8601::DATE)
+                .map(|date| PrimitiveDateTime::new(date, Time::MIDNIGHT).assume_utc())
+        })
+        .map_err(|_| "build date must be a date (YYYY-MM-DD) or an RFC 3339 timestamp".to_owned())
+}
+
+/// Print all fonts, grouped by family.
+fn print_fonts(input_dir: Option<&Path>, fonts: FontArgs) -> Result<()> {
+    let options = Options {
+        font_paths: fonts.font_paths,
+        ignore_system_fonts: fonts.ignore_system_fonts,
+        ..Options::default()
+    };
+    let project_dir = find_project(input_dir.unwrap_or(Path::new(".")))?;
+
+    let mut family = None;
+    for font in list_fonts(project_dir, &options)? {
+        if family.as_ref() != Some(&font.family) {
+            println!("{}", font.family);
+        }
+        let source = font
+            .path
+            .as_ref()
+            .map_or_else(|| "embedded".to_owned(), |path| path.display().to_string());
+        println!(
+            "- {} {} ({source}, index {})",
+            font.style, font.weight, font.index
+        );
+        family = Some(font.family);
+    }
+    Ok(())
+}
+
+/// Vendor all packages used by the project, listing them along the way.
+fn vendor_packages(input_dir: Option<&Path>, build: BuildArgs) -> Result<()> {
+    let options = Options::from(build);
+    let project_dir = find_project(input_dir.unwrap_or(Path::new(".")))?;
+
+    for package in vendor(project_dir, None, &options)? {
+        println!("vendored {package} to {}", package.path.display());
+    }
+    Ok(())
+}
+
+/// Run a package cache command.
+fn manage_cache(command: CacheCommand) -> Result<()> {
+    match command {
+        CacheCommand::List => {
+            for package in list_cached_packages()? {
+                let last_used = package.last_used.map_or_else(
+                    || "unknown".to_owned(),
+                    |last_used| OffsetDateTime::from(last_used).date().to_string(),
+                );
+                println!("{package} ({} bytes, last used {last_used})", package.size);
+            }
+        }
+        CacheCommand::Prune { input_dirs } => {
+            let project_dirs = if input_dirs.is_empty() {
+                vec![find_project(Path::new("."))?]
+            } else {
+                input_dirs
+                    .iter()
+                    .map(|input_dir| find_project(input_dir))
+                    .collect::<Result<_>>()?
+            };
+            for package in prune_package_cache(&project_dirs)? {
+                println!("removed {package} ({} bytes)", package.size);
+            }
+        }
+        CacheCommand::Clear => {
+            for package in clear_package_cache()? {
+                println!("removed {package} ({} bytes)", package.size);
+            }
+        }
+    }
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
-    render_dir(args.input_dir, args.output_dir)
+    match args.command {
+        Some(Command::Fonts { fonts, input_dir }) => {
+            return print_fonts(input_dir.as_deref(), fonts);
+        }
+        Some(Command::Vendor { build, input_dir }) => {
+            return vendor_packages(input_dir.as_deref(), build);
+        }
+        Some(Command::Cache { command }) => return manage_cache(command),
+        None => {}
+    }
+
+    let options = Options {
+        force: args.force,
+        only: args.only,
+        ..Options::from(args.build)
+    };
+
+    let project_dir = find_project(args.input_dir.as_deref().unwrap_or(Path::new(".")))?;
+
+    let report = render_dir(project_dir, args.output_dir, &options)?;
+
+    if args.timings {
+        println!("{report}");
+    }
+    if let Some(path) = args.timings_json {
+        report.write_json(&path)?;
+    }
+
+    Ok(())
 }
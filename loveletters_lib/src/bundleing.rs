@@ -1,16 +1,13 @@
-use std::{
-    fs,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
 use bytes::Bytes;
 
 use crate::{
-    error::{Error, Result},
-    page::{Index, Leaf},
+    error::Result,
     rendering::RenderedPage,
-    section::Section,
-    utils::ensure_exists,
+    report::{Phase, Recorder},
+    slug::Slug,
+    utils::{ensure_exists, write_atomically},
 };
 
 pub struct InMemFile {
@@ -23,10 +20,7 @@ impl InMemFile {
     }
 
     pub fn write_to(self, out_file: &Path) -> Result<()> {
-        fs::write(out_file, self.content).map_err(|e| Error::FileIO {
-            path: Some(out_file.to_path_buf()),
-            raw: e,
-        })
+        write_atomically(out_file, &self.content)
     }
 }
 
@@ -49,40 +43,42 @@ impl PageBundle {
     }
 }
 
-pub struct Bundler {
+pub struct Bundler<'a> {
     output_dir: PathBuf,
+    recorder: &'a Recorder,
 }
 
-impl Bundler {
-    pub fn new(output_dir: PathBuf) -> Self {
-        Self { output_dir }
+impl<'a> Bundler<'a> {
+    pub(crate) fn new(output_dir: PathBuf, recorder: &'a Recorder) -> Self {
+        Self {
+            output_dir,
+            recorder,
+        }
     }
 
-    pub fn try_bundle(
+    /// Write the page at `section` (and `page` for leaf pages) to the output directory.
+    ///
+    /// Pages are bundled as soon as they are rendered, so that a rendered page is held in memory
+    /// only briefly. Returns the page's typst file.
+    pub fn try_bundle_page<M>(
         &self,
-        content: Section<RenderedPage<Index>, RenderedPage<Leaf>>,
-    ) -> Result<()> {
-        let output_dir = self.output_dir.clone();
-        let _ = content.try_walk(
-            |section, rendering| {
-                let output_dir = section.iter().fold(output_dir.clone(), |output_dir, slug| {
-                    output_dir.join(slug.as_str())
-                });
-                ensure_exists(&output_dir)?;
-                rendering.try_bundle(output_dir)?.write_to_disk()
-            },
-            |section, page, rendering| {
-                let output_dir = section
-                    .iter()
-                    .fold(output_dir.clone(), |output_dir, slug| {
-                        output_dir.join(slug.as_str())
-                    })
-                    .join(page.as_str());
-                ensure_exists(&output_dir)?;
-                rendering.try_bundle(output_dir)?.write_to_disk()
-            },
-        )?;
+        section: &[Slug],
+        page: Option<&Slug>,
+        rendering: RenderedPage<M>,
+    ) -> Result<PathBuf> {
+        let output_dir = section
+            .iter()
+            .chain(page)
+            .fold(self.output_dir.clone(), |output_dir, slug| {
+                output_dir.join(slug.as_str())
+            });
 
-        Ok(())
+        let page = rendering.page().to_path_buf();
+        let size = rendering.size();
+        self.recorder.time(&page, Phase::Bundling, || {
+            rendering.bundle(output_dir).write_to_disk()
+        })?;
+        self.recorder.record_output(&page, size as u64);
+        Ok(page)
     }
 }
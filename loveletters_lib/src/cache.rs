@@ -0,0 +1,202 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Error as IoError,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use typst::utils::hash128;
+
+use crate::{
+    error::{Error, Result},
+    utils::{ensure_exists, write_atomically},
+};
+
+const MANIFEST_FILENAME: &str = "manifest.json";
+const PAGES_DIRNAME: &str = "pages";
+
+/// Fingerprint of (parts of) a page's inputs.
+pub type Fingerprint = u128;
+
+fn encode(fingerprint: Fingerprint) -> String {
+    format!("{fingerprint:032x}")
+}
+
+/// A file read while compiling a page.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Dependency {
+    path: PathBuf,
+    fingerprint: String,
+}
+
+impl Dependency {
+    pub fn new(path: PathBuf, content: &[u8]) -> Self {
+        Self {
+            path,
+            fingerprint: encode(hash128(content)),
+        }
+    }
+
+    fn is_unchanged(&self) -> bool {
+        fs::read(&self.path)
+            .is_ok_and(|content| encode(hash128(content.as_slice())) == self.fingerprint)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CacheEntry {
+    /// Fingerprint of the page's context (project configuration, content tree, etc.).
+    fingerprint: String,
+    /// Files read during the page's last compilation.
+    dependencies: Vec<Dependency>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    /// `loveletters` version the cache got written with.
+    version: String,
+    pages: HashMap<String, CacheEntry>,
+}
+
+/// Persistent on-disk cache of rendered pages.
+///
+/// A cached page is reused as long as its context fingerprint matches and none of the files read
+/// during its last compilation changed.
+pub struct BuildCache {
+    dir: PathBuf,
+    /// Entries from the previous build.
+    previous: HashMap<String, CacheEntry>,
+    /// Entries of the current build, i.e. the entries to persist.
+    current: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl BuildCache {
+    /// Open the cache at `dir`.
+    ///
+    /// With `force` set, previously cached pages are ignored. A missing, unreadable or outdated
+    /// cache is treated as empty.
+    pub fn open(dir: PathBuf, force: bool) -> Self {
+        let previous = if force {
+            HashMap::new()
+        } else {
+            fs::read(dir.join(MANIFEST_FILENAME))
+                .ok()
+                .and_then(|manifest| serde_json::from_slice::<Manifest>(&manifest).ok())
+                .filter(|manifest| manifest.version == env!("CARGO_PKG_VERSION"))
+                .map(|manifest| manifest.pages)
+                .unwrap_or_default()
+        };
+        Self {
+            dir,
+            previous,
+            current: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn html_filename(key: &str) -> String {
+        format!("{}.html", encode(hash128(key)))
+    }
+
+    fn pages_dir(&self) -> PathBuf {
+        self.dir.join(PAGES_DIRNAME)
+    }
+
+    /// Look up the rendered page stored under `key`, if it is still up to date.
+    pub fn lookup(&self, key: &str, fingerprint: Fingerprint) -> Option<Bytes> {
+        let entry = self.previous.get(key)?;
+        if entry.fingerprint != encode(fingerprint)
+            || !entry.dependencies.iter().all(Dependency::is_unchanged)
+        {
+            return None;
+        }
+        let html = fs::read(self.pages_dir().join(Self::html_filename(key))).ok()?;
+        self.current
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key.to_owned(), entry.clone());
+        Some(html.into())
+    }
+
+    /// Keep the entry stored under `key` without looking it up, e.g. for pages not rendered in
+    /// this build.
+    pub fn retain(&self, key: &str) {
+        if let Some(entry) = self.previous.get(key) {
+            self.current
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(key.to_owned(), entry.clone());
+        }
+    }
+
+    /// Store the rendered page `html` under `key`.
+    pub fn store(
+        &self,
+        key: &str,
+        fingerprint: Fingerprint,
+        dependencies: Vec<Dependency>,
+        html: &[u8],
+    ) -> Result<()> {
+        let pages_dir = self.pages_dir();
+        ensure_exists(&pages_dir)?;
+        let html_file = pages_dir.join(Self::html_filename(key));
+        fs::write(&html_file, html).map_err(|e| Error::FileIO {
+            path: Some(html_file),
+            raw: e,
+        })?;
+        self.current
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                key.to_owned(),
+                CacheEntry {
+                    fingerprint: encode(fingerprint),
+                    dependencies,
+                },
+            );
+        Ok(())
+    }
+
+    /// Write the current build's entries to disk, dropping all other entries.
+    pub fn persist(self) -> Result<()> {
+        let pages = self
+            .current
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let pages_dir = self.dir.join(PAGES_DIRNAME);
+        let keep: HashSet<String> = pages.keys().map(|key| Self::html_filename(key)).collect();
+        if let Ok(entries) = fs::read_dir(&pages_dir) {
+            for entry in entries.flatten() {
+                if !entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| keep.contains(name))
+                {
+                    // A stale file only wastes space, so there is no need to fail on it
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+
+        let manifest = Manifest {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            pages,
+        };
+        let manifest_file = self.dir.join(MANIFEST_FILENAME);
+        write_json(&manifest_file, &manifest)
+    }
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        ensure_exists(parent)?;
+    }
+    let json = serde_json::to_vec(value).map_err(|e| Error::FileIO {
+        path: Some(path.to_path_buf()),
+        raw: IoError::other(e),
+    })?;
+    write_atomically(path, &json)
+}
@@ -1,7 +1,22 @@
-use crate::error::{EntityKind, Error, Result};
+use crate::{
+    error::{EntityKind, Error, Result},
+    language::Language,
+    validation::{Keys, KnownKeys, Validation},
+};
 use serde::{Deserialize, Serialize};
-use std::{fs, io::ErrorKind, path::Path};
-use typst::foundations::{Dict, IntoValue, Value};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use typst::{
+    Feature, Features,
+    foundations::{Array, Dict, IntoValue, Value},
+    syntax::package::PackageVersion,
+};
+use typst_kit::package::DEFAULT_NAMESPACE;
 use url::{Position, Url};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -9,10 +24,213 @@ pub struct Config {
     title: String,
     author: String,
     root: Url,
+    /// Languages content is published in.
+    ///
+    /// The first language is the default language. An empty list denotes a monolingual project.
+    #[serde(default)]
+    languages: Vec<Language>,
+    /// Directory to render output to, relative to the project directory.
+    #[serde(default = "default_output_dir")]
+    output_dir: PathBuf,
+    /// Directory typst resolves absolute paths against, relative to the project directory.
+    ///
+    /// Has to contain the content directory.
+    #[serde(default = "default_typst_root")]
+    typst_root: PathBuf,
+    /// Inputs passed to typst as `sys.inputs`.
+    #[serde(default)]
+    inputs: BTreeMap<String, String>,
+    /// Fonts available to typst.
+    #[serde(default)]
+    fonts: FontConfig,
+    /// How typst packages are resolved.
+    #[serde(default)]
+    packages: PackageConfig,
+    /// How typst compiles pages.
+    #[serde(default)]
+    typst: TypstConfig,
+    /// Versions the project requires at least.
+    #[serde(default)]
+    requires: Requirements,
+}
+
+fn default_output_dir() -> PathBuf {
+    "public".into()
+}
+
+fn default_typst_root() -> PathBuf {
+    ".".into()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FontConfig {
+    /// Directories to search for fonts, relative to the project directory.
+    #[serde(default)]
+    paths: Vec<PathBuf>,
+    /// Whether to make fonts installed on the system available.
+    #[serde(default = "default_system_fonts")]
+    system: bool,
+}
+
+fn default_system_fonts() -> bool {
+    true
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            system: default_system_fonts(),
+        }
+    }
+}
+
+/// An optional typst feature.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TypstFeature {
+    /// HTML export, which is always enabled.
+    Html,
+    /// Additional accessibility features.
+    A11yExtras,
+}
+
+impl From<TypstFeature> for Feature {
+    fn from(feature: TypstFeature) -> Self {
+        match feature {
+            TypstFeature::Html => Feature::Html,
+            TypstFeature::A11yExtras => Feature::A11yExtras,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TypstConfig {
+    /// Features to enable in addition to HTML export.
+    #[serde(default)]
+    features: Vec<TypstFeature>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Requirements {
+    /// Minimum `loveletters` version.
+    #[serde(default)]
+    loveletters: Option<PackageVersion>,
+    /// Minimum typst version.
+    #[serde(default)]
+    typst: Option<PackageVersion>,
+}
+
+/// Just the requirements of a configuration, which are checked before anything else.
+///
+/// Configurations written for newer versions may not parse otherwise, e.g. when using features
+/// unknown to this version.
+#[derive(Deserialize)]
+struct RequirementsOnly {
+    #[serde(default)]
+    requires: Requirements,
+}
+
+/// This version of `loveletters`.
+fn loveletters_version() -> PackageVersion {
+    PackageVersion {
+        major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or_default(),
+        minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or_default(),
+        patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or_default(),
+    }
+}
+
+impl Requirements {
+    /// Check that this version of `loveletters` and typst meets the requirements.
+    fn try_check(&self) -> Result<()> {
+        for (component, required, actual) in [
+            ("loveletters", self.loveletters, loveletters_version()),
+            ("typst", self.typst, PackageVersion::compiler()),
+        ] {
+            if let Some(required) = required
+                && required > actual
+            {
+                return Err(Error::IncompatibleVersion {
+                    component,
+                    required: required.to_string(),
+                    actual: actual.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Registry packages from the `preview` namespace are downloaded from unless configured otherwise.
+const DEFAULT_REGISTRY: &str = "https://packages.typst.org";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PackageConfig {
+    /// Never download packages, only use the ones available locally.
+    #[serde(default)]
+    offline: bool,
+    /// Registry URL per namespace, e.g. `preview = "https://mirror.example.org/preview"`.
+    ///
+    /// The `preview` namespace is downloaded from `https://packages.typst.org/preview` unless
+    /// configured otherwise.
+    #[serde(default)]
+    registries: BTreeMap<String, Url>,
+    /// Timeout of a single download attempt, in seconds.
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+    /// How often to retry a failed download.
+    #[serde(default = "default_retries")]
+    retries: u32,
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+fn default_retries() -> u32 {
+    1
+}
+
+impl Default for PackageConfig {
+    fn default() -> Self {
+        Self {
+            offline: false,
+            registries: BTreeMap::new(),
+            timeout: default_timeout(),
+            retries: default_retries(),
+        }
+    }
+}
+
+impl PackageConfig {
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// URL of the archive of package `name` at `version` from `namespace`.
+    ///
+    /// Returns `None` for namespaces without registry. Only the `preview` namespace has a
+    /// registry by default.
+    pub fn archive_url(&self, namespace: &str, name: &str, version: &str) -> Option<String> {
+        let registry = match self.registries.get(namespace) {
+            Some(registry) => registry.as_str().trim_end_matches('/').to_owned(),
+            None if namespace == DEFAULT_NAMESPACE => format!("{DEFAULT_REGISTRY}/{namespace}"),
+            None => return None,
+        };
+        Some(format!("{registry}/{name}-{version}.tar.gz"))
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
 }
 
 impl Config {
-    pub fn try_read_from_disk(path: &Path) -> Result<Self> {
+    pub fn try_read_from_disk(path: &Path, validation: Validation) -> Result<Self> {
         let config: String = fs::read_to_string(path).map_err(|e| match e.kind() {
             ErrorKind::NotFound => Error::NotFound {
                 missing: EntityKind::ProjectConfig,
@@ -23,13 +241,86 @@ impl Config {
                 raw: e,
             },
         })?;
-        let config = toml::from_str(&config).map_err(|e| Error::MalformedProjectConfig {
-            location: path.to_path_buf(),
-            // No need to attach additional context, as the context is represented by
-            // the containing error
-            raw: e.into(),
+        if let Ok(RequirementsOnly { requires }) = toml::from_str(&config) {
+            requires.try_check()?;
+        }
+        let parsed = validation.try_parse::<Self>(&config, path).map_err(|e| {
+            Error::MalformedProjectConfig {
+                location: path.to_path_buf(),
+                // No need to attach additional context, as the context is represented by
+                // the containing error
+                raw: e,
+            }
         })?;
-        Ok(config)
+        Ok(parsed)
+    }
+
+    /// URL path prefix the project is served under.
+    pub fn root_path(&self) -> &str {
+        &self.root[Position::BeforePath..Position::AfterPath]
+    }
+
+    /// Languages to render, or a single `None` for monolingual projects.
+    pub fn content_languages(&self) -> Vec<Option<Language>> {
+        if self.languages.is_empty() {
+            vec![None]
+        } else {
+            self.languages.iter().cloned().map(Some).collect()
+        }
+    }
+
+    pub fn default_language(&self) -> Option<&Language> {
+        self.languages.first()
+    }
+
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    pub fn typst_root(&self) -> &Path {
+        &self.typst_root
+    }
+
+    /// Directories to search for fonts, i.e. `extra_paths` followed by the configured directories
+    /// resolved against `project_dir`.
+    pub fn font_paths(&self, project_dir: &Path, extra_paths: &[PathBuf]) -> Vec<PathBuf> {
+        extra_paths
+            .iter()
+            .cloned()
+            .chain(self.fonts.paths.iter().map(|path| project_dir.join(path)))
+            .collect()
+    }
+
+    pub fn system_fonts(&self) -> bool {
+        self.fonts.system
+    }
+
+    /// The package configuration, with offline mode forced on if `offline` is set.
+    pub fn packages(&self, offline: bool) -> PackageConfig {
+        let mut packages = self.packages.clone();
+        packages.offline |= offline;
+        packages
+    }
+
+    /// Typst features to enable, always including HTML export.
+    pub fn typst_features(&self) -> Features {
+        [Feature::Html]
+            .into_iter()
+            .chain(self.typst.features.iter().copied().map(Feature::from))
+            .collect()
+    }
+
+    /// Typst's `sys.inputs`, i.e. the configured inputs updated with `overrides`.
+    pub fn typst_inputs(&self, overrides: &[(String, String)]) -> Dict {
+        let mut inputs = Dict::new();
+        for (key, value) in self
+            .inputs
+            .iter()
+            .chain(overrides.iter().map(|(k, v)| (k, v)))
+        {
+            inputs.insert(key.as_str().into(), value.as_str().into_value());
+        }
+        inputs
     }
 
     pub fn to_typst(&self) -> Dict {
@@ -37,12 +328,20 @@ impl Config {
             title,
             author,
             root,
+            languages,
+            output_dir: _,
+            typst_root: _,
+            inputs: _,
+            fonts: _,
+            packages: _,
+            typst: _,
+            requires: _,
         } = self;
 
         let mut root_dict = Dict::new();
 
         let server: &str = &root[..Position::BeforePath];
-        let path: &str = &root[Position::BeforePath..Position::AfterPath];
+        let path: &str = self.root_path();
         root_dict.insert("server".into(), Value::Str(server.into()));
         root_dict.insert("path".into(), Value::Str(path.into()));
 
@@ -50,7 +349,51 @@ impl Config {
         d.insert("root".into(), root_dict.into_value());
         d.insert("author".into(), author.as_str().into_value());
         d.insert("title".into(), title.as_str().into_value());
+        let languages: Array = languages
+            .iter()
+            .map(|l| Value::Str(l.as_str().into()))
+            .collect();
+        d.insert("languages".into(), languages.into_value());
 
         d
     }
 }
+
+impl KnownKeys for Config {
+    const KNOWN_KEYS: Keys = Keys::Table(&[
+        ("title", Keys::Unchecked),
+        ("author", Keys::Unchecked),
+        ("root", Keys::Unchecked),
+        ("languages", Keys::Unchecked),
+        ("output_dir", Keys::Unchecked),
+        ("typst_root", Keys::Unchecked),
+        ("inputs", Keys::Unchecked),
+        ("fonts", FontConfig::KNOWN_KEYS),
+        ("packages", PackageConfig::KNOWN_KEYS),
+        ("typst", TypstConfig::KNOWN_KEYS),
+        ("requires", Requirements::KNOWN_KEYS),
+    ]);
+}
+
+impl KnownKeys for FontConfig {
+    const KNOWN_KEYS: Keys =
+        Keys::Table(&[("paths", Keys::Unchecked), ("system", Keys::Unchecked)]);
+}
+
+impl KnownKeys for PackageConfig {
+    const KNOWN_KEYS: Keys = Keys::Table(&[
+        ("offline", Keys::Unchecked),
+        ("registries", Keys::Unchecked),
+        ("timeout", Keys::Unchecked),
+        ("retries", Keys::Unchecked),
+    ]);
+}
+
+impl KnownKeys for TypstConfig {
+    const KNOWN_KEYS: Keys = Keys::Table(&[("features", Keys::Unchecked)]);
+}
+
+impl KnownKeys for Requirements {
+    const KNOWN_KEYS: Keys =
+        Keys::Table(&[("loveletters", Keys::Unchecked), ("typst", Keys::Unchecked)]);
+}
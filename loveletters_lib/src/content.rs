@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use time::{OffsetDateTime, PrimitiveDateTime};
 use typst::foundations::{Datetime, Dict, IntoValue, Value};
 
+use crate::validation::{Keys, KnownKeys};
+
 // TODO: dedicated module?
 // TODO: should (maybe) be empty instead - how to tell serde?
 #[derive(Debug, Deserialize, Serialize)]
@@ -29,6 +31,11 @@ impl IndexFrontmatter {
     }
 }
 
+impl KnownKeys for IndexFrontmatter {
+    const KNOWN_KEYS: Keys =
+        Keys::Table(&[("title", Keys::Unchecked), ("publication", Keys::Unchecked)]);
+}
+
 impl IntoValue for &IndexFrontmatter {
     fn into_value(self) -> Value {
         self.to_typst()
@@ -60,6 +67,11 @@ impl LeafFrontmatter {
     }
 }
 
+impl KnownKeys for LeafFrontmatter {
+    const KNOWN_KEYS: Keys =
+        Keys::Table(&[("title", Keys::Unchecked), ("publication", Keys::Unchecked)]);
+}
+
 impl IntoValue for &LeafFrontmatter {
     fn into_value(self) -> Value {
         self.to_typst()
@@ -2,14 +2,18 @@ use std::{
     collections::HashMap,
     marker::PhantomData,
     path::{Path, PathBuf},
+    time::Instant,
 };
 
 use crate::{
-    error::{Error, Result},
+    error::{EntityKind, Error, Result},
     frontmatter_parsing::PageWithFrontmatter,
+    language::Language,
     page::{Index, Leaf, Mode},
+    report::{Phase, Recorder},
     section::Section,
     slug::Slug,
+    validation::{KnownKeys, Validation},
 };
 use serde::Deserialize;
 use walkdir::{DirEntry, WalkDir};
@@ -19,16 +23,41 @@ static RESERVED_DIRS: [&str; 4] = ["_index", "posts", "static", "assets"];
 pub struct Discoverer {}
 
 impl Discoverer {
+    /// Locate the content tree of `language` below `content_dir`.
+    ///
+    /// Returns the tree's root directory alongside the language suffix of the tree's page files.
+    /// A language's content is read from `content_dir/<language>` if that directory exists.
+    /// Otherwise, it is read from `page.<language>.typ`/`page.<language>.toml` siblings in
+    /// `content_dir`, except for the default language which uses the plain `page.typ`/`page.toml`.
+    pub fn locate(
+        content_dir: &Path,
+        language: Option<&Language>,
+        default_language: Option<&Language>,
+    ) -> (PathBuf, Option<Language>) {
+        match language {
+            None => (content_dir.to_path_buf(), None),
+            Some(language) if content_dir.join(language.as_str()).is_dir() => {
+                (content_dir.join(language.as_str()), None)
+            }
+            Some(language) if Some(language) == default_language => {
+                (content_dir.to_path_buf(), None)
+            }
+            Some(language) => (content_dir.to_path_buf(), Some(language.clone())),
+        }
+    }
+
     pub fn try_traverse(
         content_dir: &Path,
+        suffix: Option<&Language>,
+        recorder: &Recorder,
     ) -> Result<Section<DiscoveredPage<Index>, DiscoveredPage<Leaf>>> {
         // TODO: implement recursively to collect sub-sections of arbitrary depth of arbitrary name
-        let posts = Discoverer::collect_leaf_pages(&content_dir.join("posts"))?;
-        let toplevels = Discoverer::collect_leaf_pages(content_dir)?;
+        let posts = Discoverer::collect_leaf_pages(&content_dir.join("posts"), suffix, recorder)?;
+        let toplevels = Discoverer::collect_leaf_pages(content_dir, suffix, recorder)?;
 
         let posts = Section::new(
             "posts".to_owned().into(),
-            DiscoveredPage::index_page(content_dir.join("posts").join("_index")),
+            Discoverer::locate_index_page(&content_dir.join("posts"), suffix, recorder)?,
             posts,
             HashMap::new(),
         );
@@ -36,7 +65,7 @@ impl Discoverer {
         let _ = sub_secs.insert("posts".to_owned().into(), posts);
         let toplevel_section = Section::new(
             String::new().into(),
-            DiscoveredPage::index_page(content_dir.join("_index")),
+            Discoverer::locate_index_page(content_dir, suffix, recorder)?,
             toplevels,
             sub_secs,
         );
@@ -45,12 +74,30 @@ impl Discoverer {
         Ok(toplevel_section)
     }
 
-    fn is_frontmatter<M: Mode>(entry: &DirEntry) -> bool {
+    fn locate_index_page(
+        section_dir: &Path,
+        suffix: Option<&Language>,
+        recorder: &Recorder,
+    ) -> Result<DiscoveredPage<Index>> {
+        let dir = section_dir.join("_index");
+        recorder.time(&Index::typst_file(&dir, suffix), Phase::Discovery, || {
+            let frontmatter_file = dir.join(Index::frontmatter_filename(suffix));
+            if !frontmatter_file.is_file() {
+                return Err(Error::NotFound {
+                    missing: EntityKind::Other,
+                    path: frontmatter_file,
+                });
+            }
+            Ok(DiscoveredPage::index_page(dir.clone(), suffix.cloned()))
+        })
+    }
+
+    fn is_frontmatter<M: Mode>(entry: &DirEntry, suffix: Option<&Language>) -> bool {
         entry.file_type().is_file()
             && entry
                 .file_name()
                 .to_str()
-                .is_some_and(|name| name == M::frontmatter_filename())
+                .is_some_and(|name| name == M::frontmatter_filename(suffix))
     }
 
     fn is_reserved_dir(entry: &DirEntry) -> bool {
@@ -61,13 +108,22 @@ impl Discoverer {
                 .is_some_and(|p| RESERVED_DIRS.iter().any(|d| p.ends_with(d)))
     }
 
-    fn collect_leaf_pages(dir: &Path) -> Result<HashMap<Slug, DiscoveredPage<Leaf>>> {
+    fn collect_leaf_pages(
+        dir: &Path,
+        suffix: Option<&Language>,
+        recorder: &Recorder,
+    ) -> Result<HashMap<Slug, DiscoveredPage<Leaf>>> {
+        // Walking the directory is what takes time, so attribute the time since the previous page
+        // got found to each page
+        let mut since = Instant::now();
+        // Sorting keeps the progress output independent of the filesystem's order
         WalkDir::new(dir)
+            .sort_by_file_name()
             .min_depth(2)
             .max_depth(2)
             .into_iter()
             .filter_entry(|e| {
-                Discoverer::is_frontmatter::<Leaf>(e) && !Discoverer::is_reserved_dir(e)
+                Discoverer::is_frontmatter::<Leaf>(e, suffix) && !Discoverer::is_reserved_dir(e)
             })
             .map(|entry| {
                 let entry = entry.map_err(|e| {
@@ -91,9 +147,15 @@ impl Discoverer {
 
                 println!("Collecting {}", parent_dir.display());
                 let slug: Slug = parent_dir.try_into()?;
+                recorder.record(
+                    &Leaf::typst_file(parent_dir, suffix),
+                    Phase::Discovery,
+                    since.elapsed(),
+                );
+                since = Instant::now();
                 Ok((
                     slug.clone(),
-                    DiscoveredPage::<Leaf>::leaf_page(parent_dir.to_path_buf()),
+                    DiscoveredPage::<Leaf>::leaf_page(parent_dir.to_path_buf(), suffix.cloned()),
                 ))
             })
             .collect::<Result<HashMap<_, _>>>()
@@ -103,14 +165,17 @@ impl Discoverer {
 /// Self-contained directory representing a page.
 pub struct DiscoveredPage<M> {
     content_dir: PathBuf,
+    /// Language suffix of the page's files, see [`Mode::localised_filestem`].
+    suffix: Option<Language>,
     m: PhantomData<M>,
 }
 
 impl DiscoveredPage<Index> {
     /// Read an index page for the type `K`.
-    pub fn index_page(dir: PathBuf) -> DiscoveredPage<Index> {
+    pub fn index_page(dir: PathBuf, suffix: Option<Language>) -> DiscoveredPage<Index> {
         DiscoveredPage {
             content_dir: dir,
+            suffix,
             m: PhantomData,
         }
     }
@@ -118,19 +183,25 @@ impl DiscoveredPage<Index> {
 
 impl DiscoveredPage<Leaf> {
     /// Read a leaf page from the specified directory.
-    pub fn leaf_page(dir: PathBuf) -> Self {
+    pub fn leaf_page(dir: PathBuf, suffix: Option<Language>) -> Self {
         DiscoveredPage {
             content_dir: dir,
+            suffix,
             m: PhantomData,
         }
     }
 }
 
 impl<M: Mode> DiscoveredPage<M> {
-    pub fn try_parse<F>(self) -> Result<PageWithFrontmatter<M, F>>
+    /// See [`Mode::typst_file`].
+    pub fn typst_file(&self) -> PathBuf {
+        M::typst_file(&self.content_dir, self.suffix.as_ref())
+    }
+
+    pub fn try_parse<F>(self, validation: Validation) -> Result<PageWithFrontmatter<M, F>>
     where
-        F: for<'de> Deserialize<'de>,
+        F: for<'de> Deserialize<'de> + KnownKeys,
     {
-        PageWithFrontmatter::try_parse(self.content_dir)
+        PageWithFrontmatter::try_parse(self.content_dir, self.suffix, validation)
     }
 }
@@ -21,6 +21,8 @@ pub enum EntityKind {
     ProjectConfig,
     /// A page's root content file
     TypstRoot,
+    /// A project's package lockfile
+    Lockfile,
     /// Some unspecified entity
     Other,
 }
@@ -32,6 +34,7 @@ impl Display for EntityKind {
             EntityKind::OutputDirectory => write!(f, "output directory"),
             EntityKind::ProjectConfig => write!(f, "project configuration file"),
             EntityKind::TypstRoot => write!(f, "typst root file"),
+            EntityKind::Lockfile => write!(f, "package lockfile"),
             EntityKind::Other => write!(f, "file or directory"),
         }
     }
@@ -100,12 +103,63 @@ pub enum Error {
         #[source]
         raw: anyhow::Error,
     },
+    /// Malformed package lockfile
+    #[error("failed to parse the package lockfile from '{location}'")]
+    MalformedLockfile {
+        /// The erroneous lockfile's filesystem location
+        location: PathBuf,
+        /// The underlying error
+        #[source]
+        raw: anyhow::Error,
+    },
+    /// Output directory that must not be rendered to
+    #[error("refusing to render to '{path}' because {reason}")]
+    InvalidOutputDirectory {
+        /// The rejected output directory
+        path: PathBuf,
+        /// Why the output directory got rejected
+        reason: &'static str,
+    },
+    /// Typst root that pages cannot be compiled with
+    #[error("refusing to use '{path}' as typst root because {reason}")]
+    InvalidTypstRoot {
+        /// The rejected typst root
+        path: PathBuf,
+        /// Why the typst root got rejected
+        reason: &'static str,
+    },
+    /// Project requiring a newer version of `loveletters` or typst
+    #[error("project requires {component} {required} or newer, but this is {component} {actual}")]
+    IncompatibleVersion {
+        /// The outdated component, i.e. `loveletters` or `typst`
+        component: &'static str,
+        /// The version required by the project
+        required: String,
+        /// The version at hand
+        actual: String,
+    },
     /// Malformed project structure
     #[error("detected malformed project structure at '{path}'")]
     MalformedProjectStructure {
         /// The project structure violating path
         path: PathBuf,
     },
+    /// Malformed build date, e.g. in `SOURCE_DATE_EPOCH`
+    #[error("failed to parse build date '{value}'")]
+    InvalidBuildDate {
+        /// The erroneous build date
+        value: String,
+        /// The underlying error
+        #[source]
+        raw: anyhow::Error,
+    },
+    /// Worker threads could not be set up
+    #[error("failed to set up worker threads")]
+    WorkerThreads {
+        /// The underlying error
+        #[source]
+        raw: anyhow::Error,
+    },
     /// Typst compilation failed
     #[error("failed to compile content of page at '{page}'")]
     Compilation {
@@ -0,0 +1,56 @@
+//! Fonts available to typst.
+
+use std::path::{Path, PathBuf};
+
+use typst_kit::fonts::{FontSearcher, Fonts};
+
+use crate::{config::Config, options::Options};
+
+/// A font typst may use when rendering a project.
+#[derive(Debug, Clone)]
+pub struct AvailableFont {
+    /// The font's family name.
+    pub family: String,
+    /// The font's style, e.g. `normal` or `italic`.
+    pub style: String,
+    /// The font's weight, e.g. `400` for regular fonts.
+    pub weight: u16,
+    /// File the font got loaded from, or `None` for fonts embedded into `loveletters`.
+    pub path: Option<PathBuf>,
+    /// Index of the font within its file (for font collections).
+    pub index: u32,
+}
+
+/// Search the font directories of the project at `project_dir` and, unless disabled, the system's
+/// font directories for fonts.
+///
+/// Fonts embedded into `loveletters` are always available.
+pub(crate) fn search(project_dir: &Path, config: &Config, options: &Options) -> Fonts {
+    FontSearcher::new()
+        .include_system_fonts(config.system_fonts() && !options.ignore_system_fonts)
+        .search_with(config.font_paths(project_dir, &options.font_paths))
+}
+
+/// Describe all `fonts`, ordered by family, style and weight.
+pub(crate) fn describe(fonts: &Fonts) -> Vec<AvailableFont> {
+    let mut described: Vec<_> = fonts
+        .fonts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, slot)| {
+            let info = fonts.book.info(i)?;
+            Some(AvailableFont {
+                family: info.family.clone(),
+                style: format!("{:?}", info.variant.style).to_lowercase(),
+                weight: info.variant.weight.to_number(),
+                path: slot.path().map(PathBuf::from),
+                index: slot.index(),
+            })
+        })
+        .collect();
+    described.sort_by(|a, b| {
+        (&a.family, &a.style, a.weight, &a.path, a.index)
+            .cmp(&(&b.family, &b.style, b.weight, &b.path, b.index))
+    });
+    described
+}
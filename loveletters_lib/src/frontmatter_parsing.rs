@@ -7,9 +7,12 @@ use typst::foundations::{Dict, IntoValue, Value};
 use crate::{
     discovery::DiscoveredPage,
     error::{Error, Result},
+    language::Language,
     page::Mode,
     rendering::{RenderedPage, Renderer, context::PageContext},
+    report::{Phase, Recorder},
     section::Section,
+    validation::{KnownKeys, Validation},
 };
 
 // TODO Instead of having a page with frontmatter, it might be more helpful to have a page with
@@ -20,6 +23,7 @@ use crate::{
 
 pub struct PageWithFrontmatter<M, F> {
     content_dir: PathBuf,
+    suffix: Option<Language>,
     frontmatter: F,
     m: PhantomData<M>,
 }
@@ -27,16 +31,21 @@ pub struct PageWithFrontmatter<M, F> {
 impl<M, F> PageWithFrontmatter<M, F>
 where
     M: Mode,
-    F: for<'de> Deserialize<'de>,
+    F: for<'de> Deserialize<'de> + KnownKeys,
 {
-    pub fn try_parse(dir: PathBuf) -> Result<Self> {
-        let frontmatter_file = dir.join(M::frontmatter_filename());
+    pub fn try_parse(
+        dir: PathBuf,
+        suffix: Option<Language>,
+        validation: Validation,
+    ) -> Result<Self> {
+        let frontmatter_file = dir.join(M::frontmatter_filename(suffix.as_ref()));
         let frontmatter: String =
             fs::read_to_string(&frontmatter_file).map_err(|e| Error::FileIO {
                 path: Some(frontmatter_file.clone()),
                 raw: e,
             })?;
-        let frontmatter: F = toml::from_str(&frontmatter)
+        let parsed: F = validation
+            .try_parse(&frontmatter, &frontmatter_file)
             // TODO: this context is actually redundant
             .with_context(|| "Failed to parse frontmatter.")
             .map_err(|e| Error::MalformedFrontmatter {
@@ -45,18 +54,24 @@ where
             })?;
         Ok(Self {
             content_dir: dir,
-            frontmatter,
+            suffix,
+            frontmatter: parsed,
             m: PhantomData,
         })
     }
 }
 
 impl<M, F> PageWithFrontmatter<M, F> {
-    pub fn try_render(self, renderer: &Renderer, ctx: PageContext) -> Result<RenderedPage<M>>
+    /// Render this page, unless it is not selected for rendering.
+    pub fn try_render(
+        self,
+        renderer: &Renderer,
+        ctx: PageContext,
+    ) -> Result<Option<RenderedPage<M>>>
     where
         M: Mode,
     {
-        renderer.try_render_dir(self.content_dir, ctx)
+        renderer.try_render_dir(&self.content_dir, self.suffix.as_ref(), ctx)
     }
 }
 
@@ -67,6 +82,7 @@ where
     fn into_value(self) -> Value {
         let PageWithFrontmatter {
             content_dir: _,
+            suffix: _,
             frontmatter,
             m: _,
         } = self;
@@ -81,15 +97,25 @@ where
 // In case this changes in the future, make it a method of some `Parser` type.
 pub fn try_parse<MIndex, MLeaf, FIndex, FLeaf>(
     section: Section<DiscoveredPage<MIndex>, DiscoveredPage<MLeaf>>,
+    validation: Validation,
+    recorder: &Recorder,
 ) -> Result<Section<PageWithFrontmatter<MIndex, FIndex>, PageWithFrontmatter<MLeaf, FLeaf>>>
 where
     MIndex: Mode,
     MLeaf: Mode,
-    FIndex: for<'de> Deserialize<'de>,
-    FLeaf: for<'de> Deserialize<'de>,
+    FIndex: for<'de> Deserialize<'de> + KnownKeys,
+    FLeaf: for<'de> Deserialize<'de> + KnownKeys,
 {
     section.try_map(
-        DiscoveredPage::<MIndex>::try_parse::<FIndex>,
-        DiscoveredPage::<MLeaf>::try_parse::<FLeaf>,
+        |page| {
+            recorder.time(&page.typst_file(), Phase::Frontmatter, || {
+                page.try_parse::<FIndex>(validation)
+            })
+        },
+        |page| {
+            recorder.time(&page.typst_file(), Phase::Frontmatter, || {
+                page.try_parse::<FLeaf>(validation)
+            })
+        },
     )
 }
@@ -0,0 +1,124 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use serde::{Deserialize, Serialize};
+use typst::foundations::{Dict, Value};
+
+use crate::{section::Section, slug::Slug};
+
+/// Language tag identifying one of a project's content trees (e.g. `en` or `de`).
+#[derive(Serialize, Deserialize, Hash, PartialEq, Eq, Debug, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct Language(String);
+
+impl Language {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Language {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        // Language tags end up as URL path segments as well as in filenames, so only allow
+        // characters that are safe in both.
+        if !value.is_empty()
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            Ok(Self(value))
+        } else {
+            Err(format!("invalid language tag '{value}'"))
+        }
+    }
+}
+
+impl From<Language> for String {
+    fn from(value: Language) -> Self {
+        value.0
+    }
+}
+
+impl AsRef<str> for Language {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Identifies a page across content trees of different languages.
+///
+/// Index pages are identified by their section path only, leaf pages additionally carry their
+/// [`Slug`].
+type PageKey = (Vec<Slug>, Option<Slug>);
+
+/// Index of which page is available in which language.
+pub struct Translations {
+    /// URL path prefix all pages are served under.
+    root_path: String,
+    pages: HashMap<PageKey, Vec<Language>>,
+}
+
+impl Translations {
+    pub fn new(root_path: &str) -> Self {
+        Self {
+            root_path: root_path.to_owned(),
+            pages: HashMap::new(),
+        }
+    }
+
+    /// Record all pages of `content` as being available in `language`.
+    pub fn register<I, L>(&mut self, language: &Language, content: &Section<I, L>) {
+        // Index and leaf pages are collected separately, as both visitors need unique access
+        let mut index_pages = Vec::new();
+        let mut leaf_pages = Vec::new();
+        content.for_each(
+            &mut |path, _| index_pages.push((path.to_vec(), None)),
+            &mut |path, slug, _| leaf_pages.push((path.to_vec(), Some(slug.clone()))),
+        );
+        for key in index_pages.into_iter().chain(leaf_pages) {
+            self.pages.entry(key).or_default().push(language.clone());
+        }
+    }
+
+    /// Absolute URL path of the given page in the given language (if any).
+    ///
+    /// Mirrors `abs_path` from the `@loveletters/loveletters` package.
+    pub fn url(&self, language: Option<&Language>, path: &[Slug], page: Option<&Slug>) -> String {
+        let separator = if self.root_path.ends_with('/') {
+            ""
+        } else {
+            "/"
+        };
+        let segments: Vec<&str> = language
+            .map(Language::as_str)
+            .into_iter()
+            .chain(path.iter().map(Slug::as_str))
+            .chain(page.map(Slug::as_str))
+            .collect();
+        format!("{}{separator}{}", self.root_path, segments.join("/"))
+    }
+
+    /// All translations of the given page as a mapping from language to URL.
+    pub fn to_typst(&self, path: &[Slug], page: Option<&Slug>) -> Dict {
+        let mut d = Dict::new();
+        if let Some(languages) = self.pages.get(&(path.to_vec(), page.cloned())) {
+            for language in languages {
+                d.insert(
+                    language.as_str().into(),
+                    Value::Str(self.url(Some(language), path, page).into()),
+                );
+            }
+        }
+        d
+    }
+}
@@ -1,68 +1,344 @@
 //! loveletters' heavy lifting.
 
 mod bundleing;
+mod cache;
 mod config;
 mod content;
 mod discovery;
 pub mod error;
+pub mod fonts;
 mod frontmatter_parsing;
+mod language;
+mod lockfile;
+pub mod options;
+pub mod package_cache;
 mod page;
 mod rendering;
+pub mod report;
 pub(crate) mod seal;
 mod section;
+mod selection;
 mod slug;
 mod utils;
+mod validation;
+mod vendor;
 
 use crate::{
     bundleing::Bundler,
+    cache::BuildCache,
     config::Config,
     discovery::Discoverer,
     error::{EntityKind, Error, Result},
+    fonts::AvailableFont,
     frontmatter_parsing::try_parse as try_parse_frontmatter,
-    rendering::{Renderer, context::ProjectContext},
-    utils::ensure_exists,
+    language::Translations,
+    lockfile::{LOCKFILE_FILENAME, Lockfile},
+    options::Options,
+    package_cache::CachedPackage,
+    rendering::{
+        PackageDirectories, Renderer, TypstEnvironment, TypstWorlds, context::ProjectContext,
+        evict_memoized, package_archive_directory, package_cache_directory,
+    },
+    report::{BuildReport, Recorder, ResolvedPackage},
+    selection::Selection,
+    utils::{ensure_exists, try_build_timestamp, try_prepare_output_dir, try_resolve_typst_root},
+    validation::Validation,
+    vendor::try_vendor,
+};
+use rayon::ThreadPoolBuilder;
+use std::{
+    io::ErrorKind,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
 };
-use std::{io::ErrorKind, path::PathBuf};
 
-/// Render `loveletters` project at `input_dir` and write rendered output to `output_dir`.
+/// Filename of a `loveletters` project's configuration file.
+const PROJECT_CONFIG_FILENAME: &str = "loveletters.toml";
+
+/// Name of a `loveletters` project's package directory.
+const PACKAGES_DIRNAME: &str = "packages";
+
+/// Name of a `loveletters` project's build cache directory.
+const CACHE_DIRNAME: &str = ".loveletters-cache";
+
+/// Find the `loveletters` project containing `start`.
+///
+/// Walks up the directory tree starting at `start` and returns the first directory containing a
+/// `loveletters.toml`.
 ///
 /// # Errors
 ///
-/// Returns an [`Error`] when encountering failures states as defined by [`Error`].
-pub fn render_dir(input_dir: PathBuf, output_dir: PathBuf) -> Result<()> {
-    let input_dir = &input_dir.canonicalize().map_err(|e| match e.kind() {
+/// Returns an [`Error`] when `start` cannot be resolved or none of its ancestors is a
+/// `loveletters` project.
+pub fn find_project(start: &Path) -> Result<PathBuf> {
+    let start = start.canonicalize().map_err(|e| match e.kind() {
         ErrorKind::NotFound => Error::NotFound {
             missing: EntityKind::InputDirectory,
-            path: input_dir,
+            path: start.to_path_buf(),
         },
         _ => Error::FileIO {
-            path: Some(input_dir),
+            path: Some(start.to_path_buf()),
             raw: e,
         },
     })?;
 
-    let output_dir = &output_dir.canonicalize().map_err(|e| match e.kind() {
+    start
+        .ancestors()
+        .find(|dir| dir.join(PROJECT_CONFIG_FILENAME).is_file())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| Error::NotFound {
+            missing: EntityKind::ProjectConfig,
+            path: start.clone(),
+        })
+}
+
+/// Canonicalize the project directory `input_dir` and read the project's configuration.
+fn try_open_project(input_dir: PathBuf, validation: Validation) -> Result<(PathBuf, Config)> {
+    let input_dir = input_dir.canonicalize().map_err(|e| match e.kind() {
         ErrorKind::NotFound => Error::NotFound {
-            missing: EntityKind::OutputDirectory,
-            path: output_dir,
+            missing: EntityKind::InputDirectory,
+            path: input_dir,
         },
         _ => Error::FileIO {
-            path: Some(output_dir),
+            path: Some(input_dir),
             raw: e,
         },
     })?;
-    ensure_exists(output_dir)?;
+
+    let config = Config::try_read_from_disk(&input_dir.join(PROJECT_CONFIG_FILENAME), validation)?;
+
+    Ok((input_dir, config))
+}
+
+fn validation(options: &Options) -> Validation {
+    if options.strict {
+        Validation::Strict
+    } else {
+        Validation::Lenient
+    }
+}
+
+/// List the fonts available when rendering the `loveletters` project at `input_dir`.
+///
+/// Takes the project's font configuration as well as the font related [`Options`] into account.
+///
+/// # Errors
+///
+/// Returns an [`Error`] when the project's configuration cannot be read.
+pub fn list_fonts(input_dir: PathBuf, options: &Options) -> Result<Vec<AvailableFont>> {
+    let (input_dir, config) = try_open_project(input_dir, validation(options))?;
+    let fonts = fonts::search(&input_dir, &config, options);
+    Ok(fonts::describe(&fonts))
+}
+
+/// List all packages in the package cache which `loveletters` used, ordered by specification.
+///
+/// The package cache is shared with the typst CLI. Packages only the typst CLI used are not
+/// listed, and hence never pruned or cleared either.
+///
+/// # Errors
+///
+/// Returns an [`Error`] when the package cache cannot be read.
+pub fn list_cached_packages() -> Result<Vec<CachedPackage>> {
+    package_cache::try_list(&package_cache_directory(), &package_archive_directory())
+}
+
+/// Remove all [listed](list_cached_packages) packages from the package cache not recorded in the
+/// lockfile of any of the `loveletters` projects at `project_dirs`.
+///
+/// Returns the removed packages.
+///
+/// # Errors
+///
+/// Returns an [`Error`] when a project has no lockfile, a lockfile cannot be read or a package
+/// cannot be removed.
+pub fn prune_package_cache(project_dirs: &[PathBuf]) -> Result<Vec<CachedPackage>> {
+    let locks = project_dirs
+        .iter()
+        .map(|project_dir| {
+            let path = project_dir.join(LOCKFILE_FILENAME);
+            // A missing lockfile would not reference any package, pruning everything
+            if !path.is_file() {
+                return Err(Error::NotFound {
+                    missing: EntityKind::Lockfile,
+                    path,
+                });
+            }
+            Lockfile::try_open(path, false)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut removed = Vec::new();
+    for package in list_cached_packages()? {
+        let spec = package.to_string();
+        if !locks.iter().any(|lock| lock.contains(&spec)) {
+            package.try_remove()?;
+            removed.push(package);
+        }
+    }
+    Ok(removed)
+}
+
+/// Remove all [listed](list_cached_packages) packages from the package cache.
+///
+/// Returns the removed packages.
+///
+/// # Errors
+///
+/// Returns an [`Error`] when the package cache cannot be read or a package cannot be removed.
+pub fn clear_package_cache() -> Result<Vec<CachedPackage>> {
+    let packages = list_cached_packages()?;
+    for package in &packages {
+        package.try_remove()?;
+    }
+    Ok(packages)
+}
+
+/// Render `loveletters` project at `input_dir` and write rendered output to `output_dir`.
+///
+/// If no `output_dir` is given, output is written to the output directory configured in the
+/// project's `loveletters.toml` (`public/` by default). The output directory is created if it
+/// does not exist yet.
+///
+/// Returns a [`BuildReport`] with statistics such as the time spent on each page.
+///
+/// # Errors
+///
+/// Returns an [`Error`] when encountering failures states as defined by [`Error`].
+pub fn render_dir(
+    input_dir: PathBuf,
+    output_dir: Option<PathBuf>,
+    options: &Options,
+) -> Result<BuildReport> {
+    let (input_dir, config) = try_open_project(input_dir, validation(options))?;
+    try_build(&input_dir, &config, output_dir, options)
+}
+
+/// Vendor all packages used by the `loveletters` project at `input_dir` into the project.
+///
+/// The project is rendered as by [`render_dir`], bypassing the build cache so that all pages get
+/// compiled. Every package resolved along the way (apart from packages embedded into
+/// `loveletters`) is then copied to `packages/<namespace>/<name>/<version>` inside the project
+/// directory, where it takes precedence over the package cache and downloads.
+///
+/// Returns the vendored packages.
+///
+/// # Errors
+///
+/// Returns an [`Error`] when the project cannot be rendered or a package cannot be copied.
+pub fn vendor(
+    input_dir: PathBuf,
+    output_dir: Option<PathBuf>,
+    options: &Options,
+) -> Result<Vec<ResolvedPackage>> {
+    let options = Options {
+        force: true,
+        only: Vec::new(),
+        ..options.clone()
+    };
+    let (input_dir, config) = try_open_project(input_dir, validation(&options))?;
+    let report = try_build(&input_dir, &config, output_dir, &options)?;
+    try_vendor(&input_dir.join(PACKAGES_DIRNAME), report.packages)
+}
+
+/// Render the project at `input_dir`, configured by `config`, see [`render_dir`].
+fn try_build(
+    input_dir: &Path,
+    config: &Config,
+    output_dir: Option<PathBuf>,
+    options: &Options,
+) -> Result<BuildReport> {
+    let validation = validation(options);
 
     let content_dir = input_dir.join("content");
 
-    let config = Config::try_read_from_disk(&input_dir.join("loveletters.toml"))?;
+    let output_dir = output_dir.unwrap_or_else(|| input_dir.join(config.output_dir()));
+    let output_dir = &try_prepare_output_dir(input_dir, &content_dir, &output_dir)?;
+    let typst_root = try_resolve_typst_root(&content_dir, &input_dir.join(config.typst_root()))?;
+
+    let recorder = Recorder::default();
+
+    // Parse all languages' content upfront, so that every page knows about its translations
+    let mut trees = Vec::new();
+    for language in config.content_languages() {
+        let (tree_dir, suffix) =
+            Discoverer::locate(&content_dir, language.as_ref(), config.default_language());
+        let discovered_content = Discoverer::try_traverse(&tree_dir, suffix.as_ref(), &recorder)?;
+        let frontmatter = try_parse_frontmatter(discovered_content, validation, &recorder)?;
+        trees.push((language, frontmatter));
+    }
+
+    let timestamp = try_build_timestamp(options.build_date)?;
+    let packages = config.packages(options.offline);
+    let lock = Lockfile::try_open(input_dir.join(LOCKFILE_FILENAME), options.update_lock)?;
+    let fonts = fonts::search(input_dir, config, options);
+    let environment = TypstEnvironment::new(
+        config.typst_inputs(&options.inputs),
+        config.typst_features(),
+        fonts,
+        timestamp,
+    );
+
+    let mut translations = Translations::new(config.root_path());
+    for (language, frontmatter) in &trees {
+        if let Some(language) = language {
+            translations.register(language, frontmatter);
+        }
+    }
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(options.jobs.map_or(0, NonZeroUsize::get))
+        .build()
+        .map_err(|e| Error::WorkerThreads { raw: e.into() })?;
+
+    let cache = BuildCache::open(input_dir.join(CACHE_DIRNAME), options.force);
+    let selection = Selection::new(input_dir.to_path_buf(), content_dir.clone(), &options.only);
+
+    // Engines and renderers borrow the cache, the lockfile and the recorder, so they are confined
+    // to this block, which ends before those are consumed
+    let rendered = {
+        let worlds = TypstWorlds::new(
+            &environment,
+            typst_root,
+            PackageDirectories::new(input_dir.join(PACKAGES_DIRNAME)),
+            &packages,
+            &lock,
+            &recorder,
+        );
+        let rendered = pool.install(|| {
+            for (language, frontmatter) in trees {
+                let output_dir = match &language {
+                    None => output_dir.clone(),
+                    Some(language) => output_dir.join(language.as_str()),
+                };
+                ensure_exists(&output_dir)?;
+                let bundler = Bundler::new(output_dir, &recorder);
+
+                let global_ctx =
+                    ProjectContext::new(&frontmatter, config.clone(), language, timestamp.date());
+                let renderer = Renderer::new(
+                    global_ctx,
+                    &worlds,
+                    &translations,
+                    &cache,
+                    &recorder,
+                    &selection,
+                );
+                renderer.try_render(frontmatter, &bundler)?;
+            }
+
+            Ok(())
+        });
+
+        // Memoized results are kept across builds within the same process, but only for a while
+        evict_memoized();
+        rendered
+    };
 
-    let bundler = Bundler::new(output_dir.clone());
+    // Persist the cache even on failure, so that successfully rendered pages are reused next time.
+    // Likewise, checksums of packages downloaded so far are recorded.
+    let persisted = cache.persist().and(lock.persist());
+    rendered.and(persisted)?;
 
-    let discovered_content = Discoverer::try_traverse(&content_dir)?;
-    let frontmatter = try_parse_frontmatter(discovered_content)?;
-    let global_ctx = ProjectContext::new(&frontmatter, config);
-    let renderer = Renderer::new(global_ctx, input_dir.join("packages"));
-    let rendering = renderer.try_render(frontmatter)?;
-    bundler.try_bundle(rendering)
+    Ok(recorder.finish())
 }
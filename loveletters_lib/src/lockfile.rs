@@ -0,0 +1,202 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Error as IoError, ErrorKind},
+    path::PathBuf,
+    result,
+    sync::{Mutex, PoisonError},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{Error, Result},
+    utils::write_atomically,
+};
+
+/// Filename of a `loveletters` project's lockfile.
+pub const LOCKFILE_FILENAME: &str = "loveletters.lock";
+
+const LOCKFILE_VERSION: u32 = 1;
+
+const LOCKFILE_HEADER: &str = "# This file is generated by loveletters. Do not edit it by hand, pass `--update-lock` instead.\n";
+
+/// SHA-256 checksum of `content`, as lowercase hex string.
+fn checksum(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+#[derive(Serialize, Deserialize)]
+struct Content {
+    version: u32,
+    /// Checksum of every package archive, keyed by package specification.
+    #[serde(default)]
+    checksums: BTreeMap<String, String>,
+}
+
+struct State {
+    checksums: BTreeMap<String, String>,
+    changed: bool,
+}
+
+/// Checksums of all package archives a project got built with.
+///
+/// Archives are verified against the recorded checksums. Recording new checksums or changing
+/// existing ones is only allowed when updating the lockfile explicitly, or when creating it in the
+/// first place.
+pub struct Lockfile {
+    path: PathBuf,
+    /// Whether checksums may be recorded or changed.
+    update: bool,
+    state: Mutex<State>,
+}
+
+impl Lockfile {
+    /// Open the lockfile at `path`, treating a missing lockfile as empty.
+    pub fn try_open(path: PathBuf, update: bool) -> Result<Self> {
+        let (checksums, update) = match fs::read_to_string(&path) {
+            Ok(content) => {
+                let content: Content =
+                    toml::from_str(&content).map_err(|e| Error::MalformedLockfile {
+                        location: path.clone(),
+                        raw: e.into(),
+                    })?;
+                if content.version != LOCKFILE_VERSION {
+                    return Err(Error::MalformedLockfile {
+                        location: path,
+                        raw: anyhow::anyhow!("unsupported lockfile version {}", content.version),
+                    });
+                }
+                (content.checksums, update)
+            }
+            // A missing lockfile is created from scratch
+            Err(e) if e.kind() == ErrorKind::NotFound => (BTreeMap::new(), true),
+            Err(e) => {
+                return Err(Error::FileIO {
+                    path: Some(path),
+                    raw: e,
+                });
+            }
+        };
+        Ok(Self {
+            path,
+            update,
+            state: Mutex::new(State {
+                checksums,
+                changed: false,
+            }),
+        })
+    }
+
+    /// Whether a checksum is recorded for `package`.
+    pub fn contains(&self, package: &str) -> bool {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .checksums
+            .contains_key(package)
+    }
+
+    /// Verify the archive of `package` against its recorded checksum.
+    ///
+    /// Returns a description of the problem if the archive does not match.
+    pub fn verify(&self, package: &str, archive: &[u8]) -> result::Result<(), String> {
+        let actual = checksum(archive);
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.checksums.get(package) {
+            Some(expected) if *expected == actual => return Ok(()),
+            Some(expected) if !self.update => {
+                return Err(format!(
+                    "checksum of {package} ({actual}) does not match the checksum recorded in {LOCKFILE_FILENAME} ({expected}); pass `--update-lock` if the change is intended"
+                ));
+            }
+            None if !self.update => {
+                return Err(format!(
+                    "{package} is not recorded in {LOCKFILE_FILENAME}; pass `--update-lock` to record it"
+                ));
+            }
+            _ => {}
+        }
+        state.checksums.insert(package.to_owned(), actual);
+        state.changed = true;
+        Ok(())
+    }
+
+    /// Write the lockfile to disk, if any checksum got recorded or changed.
+    pub fn persist(self) -> Result<()> {
+        let state = self
+            .state
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !state.changed {
+            return Ok(());
+        }
+        let content = toml::to_string(&Content {
+            version: LOCKFILE_VERSION,
+            checksums: state.checksums,
+        })
+        .map_err(|e| Error::FileIO {
+            path: Some(self.path.clone()),
+            raw: IoError::other(e),
+        })?;
+        write_atomically(&self.path, format!("{LOCKFILE_HEADER}{content}").as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LOCKFILE_FILENAME, Lockfile};
+    use crate::utils::TestDir;
+
+    const PACKAGE: &str = "@preview/example:0.1.0";
+
+    /// Open the lockfile in `dir`, record `packages` and persist it, as a build would.
+    fn build(dir: &TestDir, update: bool, packages: &[(&str, &[u8])]) -> Result<(), String> {
+        let lock = Lockfile::try_open(dir.path().join(LOCKFILE_FILENAME), update)
+            .map_err(|e| e.to_string())?;
+        for (package, archive) in packages {
+            lock.verify(package, archive)?;
+        }
+        lock.persist().map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn missing_lockfile_records_packages() {
+        let dir = TestDir::new("lockfile-missing");
+        assert!(build(&dir, false, &[(PACKAGE, b"archive")]).is_ok());
+        assert!(dir.path().join(LOCKFILE_FILENAME).is_file());
+        assert!(build(&dir, false, &[(PACKAGE, b"archive")]).is_ok());
+    }
+
+    #[test]
+    fn unchanged_lockfile_is_not_written() {
+        let dir = TestDir::new("lockfile-unchanged");
+        assert!(build(&dir, false, &[]).is_ok());
+        assert!(!dir.path().join(LOCKFILE_FILENAME).exists());
+    }
+
+    #[test]
+    fn new_package_requires_update() {
+        let dir = TestDir::new("lockfile-new");
+        build(&dir, false, &[(PACKAGE, b"archive")]).unwrap();
+
+        let other = "@preview/other:0.1.0";
+        let error = build(&dir, false, &[(other, b"other")]).unwrap_err();
+        assert!(error.contains("not recorded"), "{error}");
+        assert!(build(&dir, true, &[(other, b"other")]).is_ok());
+        assert!(build(&dir, false, &[(PACKAGE, b"archive"), (other, b"other")]).is_ok());
+    }
+
+    #[test]
+    fn mismatched_checksum_requires_update() {
+        let dir = TestDir::new("lockfile-mismatch");
+        build(&dir, false, &[(PACKAGE, b"archive")]).unwrap();
+
+        let error = build(&dir, false, &[(PACKAGE, b"tampered")]).unwrap_err();
+        assert!(error.contains("does not match"), "{error}");
+        assert!(build(&dir, true, &[(PACKAGE, b"changed")]).is_ok());
+        assert!(build(&dir, false, &[(PACKAGE, b"changed")]).is_ok());
+        assert!(build(&dir, false, &[(PACKAGE, b"archive")]).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+//! Knobs to tweak how a `loveletters` project is rendered.
+
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use time::OffsetDateTime;
+
+/// Options for rendering a `loveletters` project.
+// The flags are independent of each other, so there is no state machine to be had
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Fail on unknown keys in the project configuration and frontmatter instead of warning about
+    /// them.
+    pub strict: bool,
+    /// Inputs passed to typst as `sys.inputs`, taking precedence over the inputs configured in the
+    /// project configuration.
+    pub inputs: Vec<(String, String)>,
+    /// Number of pages to render concurrently, defaults to the number of available CPUs.
+    pub jobs: Option<NonZeroUsize>,
+    /// Render all pages, even if they are unchanged since the previous build.
+    pub force: bool,
+    /// Additional directories to search for fonts, searched before the directories configured in
+    /// the project configuration.
+    pub font_paths: Vec<PathBuf>,
+    /// Do not make fonts installed on the system available, regardless of the project
+    /// configuration.
+    pub ignore_system_fonts: bool,
+    /// Timestamp of the build, taking precedence over the `SOURCE_DATE_EPOCH` environment
+    /// variable. Defaults to the current time.
+    pub build_date: Option<OffsetDateTime>,
+    /// Glob patterns selecting the pages to render by content path or URL path. The full content
+    /// tree is read regardless, but only the selected pages are rendered and written. No patterns
+    /// select all pages.
+    pub only: Vec<String>,
+    /// Never download packages, regardless of the project configuration.
+    pub offline: bool,
+    /// Record new or changed package checksums in the project's lockfile instead of failing on
+    /// them.
+    pub update_lock: bool,
+}
@@ -0,0 +1,148 @@
+//! Packages downloaded to the package cache.
+
+use std::{
+    fmt::{self, Display},
+    fs::{self, Metadata},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use walkdir::WalkDir;
+
+use crate::{
+    error::{Error, Result},
+    rendering::lock_cache_entry,
+};
+
+/// A package in the package cache.
+#[derive(Debug, Clone)]
+pub struct CachedPackage {
+    /// The package's namespace, e.g. `preview`.
+    pub namespace: String,
+    /// The package's name.
+    pub name: String,
+    /// The package's version, e.g. `0.1.0`.
+    pub version: String,
+    /// Directory the package is unpacked to.
+    pub path: PathBuf,
+    /// Size of the unpacked package and its archive in bytes.
+    pub size: u64,
+    /// When the package got last used by `loveletters`, if known.
+    pub last_used: Option<SystemTime>,
+    /// Entry of the package in the archive directory, which its archive and lock are named after.
+    entry: PathBuf,
+}
+
+impl CachedPackage {
+    /// Files kept in the archive directory, i.e. the package's archive and lock.
+    fn siblings(&self) -> [PathBuf; 2] {
+        ["tar.gz", "lock"].map(|extension| {
+            self.entry
+                .with_file_name(format!("{}.{extension}", self.version))
+        })
+    }
+
+    /// Remove the package and its archive from the cache.
+    ///
+    /// The package is locked against concurrent builds while it gets removed. Its lock is kept, so
+    /// that builds waiting for the lock hold the same lock as later builds.
+    pub(crate) fn try_remove(&self) -> Result<()> {
+        let _lock = lock_cache_entry(&self.entry)?;
+        let [archive, _] = self.siblings();
+        for result in [
+            (fs::remove_dir_all(&self.path), &self.path),
+            (fs::remove_file(&archive), &archive),
+        ] {
+            match result {
+                (Err(e), path) if e.kind() != ErrorKind::NotFound => {
+                    return Err(Error::FileIO {
+                        path: Some(path.clone()),
+                        raw: e,
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for CachedPackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}/{}:{}", self.namespace, self.name, self.version)
+    }
+}
+
+/// Visible subdirectories of `dir`, with their names.
+///
+/// Temporary directories of unfinished downloads are hidden and hence skipped.
+fn try_subdirs(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(Error::FileIO {
+                path: Some(dir.to_path_buf()),
+                raw: e,
+            });
+        }
+    };
+
+    let mut subdirs = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::FileIO {
+            path: Some(dir.to_path_buf()),
+            raw: e,
+        })?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with('.') && entry.path().is_dir() {
+            subdirs.push((name, entry.path()));
+        }
+    }
+    subdirs.sort();
+    Ok(subdirs)
+}
+
+/// Total size of all files below `path` in bytes.
+fn size_of(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok()?.metadata().ok())
+        .filter(Metadata::is_file)
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// List all packages in the package cache at `cache_dir` which `loveletters` used, ordered by
+/// specification.
+///
+/// Packages are found at `<namespace>/<name>/<version>`. `loveletters` used a package if there is
+/// a lock for it in `archive_dir`. Packages only the typst CLI used are skipped. A package's last
+/// use is the modification time of its lock, which is refreshed whenever a build uses the package.
+pub(crate) fn try_list(cache_dir: &Path, archive_dir: &Path) -> Result<Vec<CachedPackage>> {
+    let mut packages = Vec::new();
+    for (namespace, namespace_dir) in try_subdirs(cache_dir)? {
+        for (name, name_dir) in try_subdirs(&namespace_dir)? {
+            for (version, path) in try_subdirs(&name_dir)? {
+                let mut package = CachedPackage {
+                    entry: archive_dir.join(&namespace).join(&name).join(&version),
+                    namespace: namespace.clone(),
+                    name: name.clone(),
+                    version,
+                    path,
+                    size: 0,
+                    last_used: None,
+                };
+                let [archive, lock] = package.siblings();
+                let Ok(lock_metadata) = fs::metadata(&lock) else {
+                    continue;
+                };
+                package.size = size_of(&package.path) + size_of(&archive);
+                package.last_used = lock_metadata.modified().ok();
+                packages.push(package);
+            }
+        }
+    }
+    Ok(packages)
+}
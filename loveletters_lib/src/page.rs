@@ -1,4 +1,6 @@
-use crate::seal::Seal;
+use std::path::{Path, PathBuf};
+
+use crate::{language::Language, seal::Seal};
 
 pub struct Index;
 
@@ -6,11 +8,22 @@ pub struct Leaf;
 
 pub trait Mode: Seal {
     fn frontmatter_filestem() -> String;
-    fn frontmatter_filename() -> String {
-        Self::frontmatter_filestem() + ".toml"
+    /// Filestem of this mode's files, optionally suffixed with a language (e.g. `page.de`).
+    fn localised_filestem(suffix: Option<&Language>) -> String {
+        match suffix {
+            None => Self::frontmatter_filestem(),
+            Some(language) => format!("{}.{language}", Self::frontmatter_filestem()),
+        }
+    }
+    fn frontmatter_filename(suffix: Option<&Language>) -> String {
+        Self::localised_filestem(suffix) + ".toml"
+    }
+    fn typst_filename(suffix: Option<&Language>) -> String {
+        Self::localised_filestem(suffix) + ".typ"
     }
-    fn typst_filename() -> String {
-        Self::frontmatter_filestem() + ".typ"
+    /// Location of the typst file of the page at `dir`, identifying the page within a build.
+    fn typst_file(dir: &Path, suffix: Option<&Language>) -> PathBuf {
+        dir.join(Self::typst_filename(suffix))
     }
 }
 
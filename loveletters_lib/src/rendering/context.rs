@@ -1,9 +1,11 @@
-use typst::foundations::{Dict, IntoValue, Str, Value};
+use time::Date;
+use typst::foundations::{Datetime, Dict, IntoValue, Str, Value};
 
 use crate::{
     config::Config,
     content::{IndexFrontmatter, LeafFrontmatter},
     frontmatter_parsing::PageWithFrontmatter,
+    language::{Language, Translations},
     page::{Index, Leaf},
     section::Section,
     slug::Slug,
@@ -13,6 +15,9 @@ use crate::{
 pub struct ProjectContext {
     content: Dict,
     config: Config,
+    language: Option<Language>,
+    /// The build's date (UTC).
+    build_date: Date,
 }
 
 impl ProjectContext {
@@ -22,36 +27,67 @@ impl ProjectContext {
             PageWithFrontmatter<Leaf, LeafFrontmatter>,
         >,
         config: Config,
+        language: Option<Language>,
+        build_date: Date,
     ) -> Self {
         Self {
             content: content.to_typst(),
             config,
+            language,
+            build_date,
         }
     }
 }
 
+impl ProjectContext {
+    pub fn language(&self) -> Option<&Language> {
+        self.language.as_ref()
+    }
+}
+
 impl IntoValue for ProjectContext {
     fn into_value(self) -> Value {
-        let Self { content, config } = self;
+        let Self {
+            content,
+            config,
+            language,
+            build_date,
+        } = self;
         let mut d = Dict::new();
         d.insert("content".into(), content.into_value());
         d.insert("config".into(), config.to_typst().into_value());
+        d.insert(
+            "language".into(),
+            language.map_or(Value::None, |l| Value::Str(l.as_str().into())),
+        );
+        d.insert("build_date".into(), Datetime::Date(build_date).into_value());
         d.into_value()
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct PageContext<'a> {
     section_path: &'a [Slug],
     page: Option<&'a Slug>,
+    translations: &'a Translations,
 }
 
 impl<'a> PageContext<'a> {
-    pub fn new(path: &'a [Slug], page: Option<&'a Slug>) -> Self {
+    pub fn new(path: &'a [Slug], page: Option<&'a Slug>, translations: &'a Translations) -> Self {
         Self {
             section_path: path,
             page,
+            translations,
         }
     }
+
+    pub fn section_path(&self) -> &'a [Slug] {
+        self.section_path
+    }
+
+    pub fn page(&self) -> Option<&'a Slug> {
+        self.page
+    }
 }
 
 impl IntoValue for PageContext<'_> {
@@ -67,6 +103,9 @@ impl IntoValue for PageContext<'_> {
         if let Some(page) = self.page {
             d.insert("page".into(), Value::Str(page.as_str().into()));
         }
+
+        let translations = self.translations.to_typst(self.section_path, self.page);
+        d.insert("translations".into(), Value::Dict(translations));
         d.into_value()
     }
 }
@@ -5,104 +5,373 @@
 // - annotations of future todos
 // - adaptions in the way `TypstEngine` (formerly known as `TypstWrapperWorld`) is instantiated
 // - lookup procedure for packages from the `loveletters` namespace
+// - build-wide sharing of fonts, the standard library and package files via `TypstEnvironment`
+// - long-lived engines swapping the main source between compilations via `TypstWorlds`
+// - a single, possibly fixed, timestamp per build
+// - the `@loveletters/loveletters` package being embedded into the binary
+// - a project-wide root shared by all pages
+// - verification of package archives against the project's lockfile
+// - lookup of packages vendored into the project, for all namespaces
+// - lookup of user packages (e.g. `@local`) and cached downloads in typst's standard directories
+// - atomic, locked population of the package cache
 
 use std::{
-    collections::HashMap,
-    env::{temp_dir, var_os},
-    ffi::OsString,
-    fs::{read as read_file, read_to_string, remove_dir_all},
+    collections::{HashMap, hash_map::Entry},
+    env::temp_dir,
+    fs::{
+        File, OpenOptions, create_dir_all, read as read_file, read_to_string, remove_dir_all,
+        rename,
+    },
     io::{ErrorKind, Read},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::from_utf8,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Instant, SystemTime},
 };
 
 use typst::diag::{FileError, FileResult, PackageError, PackageResult, eco_format};
 use typst::foundations::{Bytes, Datetime, Dict, IntoValue};
 use typst::syntax::package::PackageSpec;
-use typst::syntax::{FileId, Source};
+use typst::syntax::{FileId, Source, VirtualPath};
 use typst::text::{Font, FontBook};
-use typst::utils::LazyHash;
-use typst::{Feature, Library, LibraryExt};
-use typst_kit::fonts::{FontSearcher, FontSlot};
-use ureq::agent;
+use typst::utils::{LazyHash, hash128};
+use typst::{Features, Library, LibraryExt};
+use typst_kit::{
+    fonts::{FontSlot, Fonts},
+    package::DEFAULT_PACKAGES_SUBDIR,
+};
+use ureq::{Agent, Proxy};
 
 use crate::{
+    cache::{Dependency, Fingerprint},
+    config::PackageConfig,
     error::{EntityKind, Error, Result as CrateResult},
+    lockfile::Lockfile,
     rendering::context::{PageContext, ProjectContext},
+    report::{Recorder, ResolvedPackage},
+    utils::{temporary_sibling, write_atomically},
 };
 
 // TODO: check that pathbuf actually is relative (maybe use VirtualPath instead?)!
-type RelativePath = PathBuf;
+pub type RelativePath = PathBuf;
+
+/// Subdirectory of the user's cache directory package archives are kept in.
+///
+/// Unpacked packages are shared with the typst CLI, which does not keep the archives around. Hence,
+/// the archives are kept apart, in a directory owned by `loveletters` alone.
+const ARCHIVES_SUBDIR: &str = "loveletters/archives";
+
+/// Filename of a package's manifest, which every complete package contains.
+const MANIFEST_FILENAME: &str = "typst.toml";
+
+/// Files of a package, as pairs of path (relative to the package root) and content.
+type PackageFiles = &'static [(&'static str, &'static [u8])];
+
+/// Versions of the `@loveletters/loveletters` package embedded into the binary.
+const EMBEDDED_UTIL_PACKAGES: &[(&str, PackageFiles)] = &[(
+    "0.1.0",
+    &[
+        (
+            "typst.toml",
+            include_bytes!("../../../loveletters_util/typst.toml"),
+        ),
+        (
+            "lib.typ",
+            include_bytes!("../../../loveletters_util/lib.typ"),
+        ),
+    ],
+)];
+
+/// Build-wide state shared by all page compilations.
+///
+/// Searching fonts and setting up the standard library is expensive, so this is done once per
+/// build instead of once per page.
+pub struct TypstEnvironment {
+    /// The standard library, without any page-specific bindings.
+    library: Library,
+
+    /// Metadata about all known fonts.
+    book: LazyHash<FontBook>,
+
+    /// All known fonts.
+    fonts: Vec<FontSlot>,
+
+    /// Fingerprint of the build-wide settings affecting compilation results.
+    fingerprint: Fingerprint,
+
+    // TODO maybe use a different time library for this?
+    /// The build's timestamp, shared by all pages.
+    timestamp: time::OffsetDateTime,
+
+    /// Files from packages, shared by all pages.
+    ///
+    /// Every file has a lock of its own, so that resolving a package (which may involve
+    /// downloading it) only blocks pages waiting for the very same file.
+    package_files: Mutex<HashMap<FileId, Arc<Mutex<Option<FileEntry>>>>>,
+
+    /// Directories of the packages resolved so far, so that each package is verified only once.
+    package_dirs: Mutex<HashMap<PackageSpec, PathBuf>>,
+}
+
+impl TypstEnvironment {
+    pub fn new(
+        inputs: Dict,
+        features: Features,
+        fonts: Fonts,
+        timestamp: time::OffsetDateTime,
+    ) -> Self {
+        // Pages observe the date at whole-hour offsets (see `World::today`), which only changes on
+        // the hour. Hence, a build within the same hour may reuse cached pages.
+        let fingerprint = hash128(&(
+            &inputs,
+            &features,
+            &fonts.book,
+            timestamp.date(),
+            timestamp.hour(),
+        ));
+        let library = Library::builder()
+            .with_inputs(inputs)
+            .with_features(features)
+            .build();
+
+        Self {
+            library,
+            book: LazyHash::new(fonts.book),
+            fonts: fonts.fonts,
+            fingerprint,
+            timestamp,
+            package_files: Mutex::new(HashMap::new()),
+            package_dirs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+}
+
+/// Number of builds a memoized result survives without being used.
+const MEMOIZATION_MAX_AGE: usize = 10;
+
+/// Evict memoized compilation results not used during the last couple of builds.
+///
+/// Mirrors the typst CLI's watch mode, which evicts after every compilation.
+pub fn evict_memoized() {
+    comemo::evict(MEMOIZATION_MAX_AGE);
+}
+
+/// Directory downloaded packages are cached in, shared with the typst CLI.
+///
+/// This is `typst/packages` inside the user's cache directory (e.g. `$XDG_CACHE_HOME` on Linux),
+/// or inside the temporary directory if there is no such directory.
+pub fn package_cache_directory() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(temp_dir)
+        .join(DEFAULT_PACKAGES_SUBDIR)
+}
+
+/// Directory archives of downloaded packages are kept in, owned by `loveletters` alone.
+///
+/// This is `loveletters/archives` inside the user's cache directory, next to the locks of all
+/// packages `loveletters` downloaded or reused from the package cache.
+pub fn package_archive_directory() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(temp_dir)
+        .join(ARCHIVES_SUBDIR)
+}
+
+/// Directories packages are looked up in, following the typst CLI's conventions.
+#[derive(Clone)]
+pub struct PackageDirectories {
+    /// Packages vendored into the project, taking precedence over all other sources.
+    project: PathBuf,
+
+    /// Packages installed by the user (e.g. `@local` packages), at `typst/packages` inside the
+    /// user's data directory (e.g. `$XDG_DATA_HOME` on Linux).
+    user: Option<PathBuf>,
+
+    /// Downloaded packages, see [`package_cache_directory`].
+    cache: PathBuf,
+
+    /// Archives of downloaded packages, see [`package_archive_directory`].
+    archives: PathBuf,
+}
+
+impl PackageDirectories {
+    pub fn new(project: PathBuf) -> Self {
+        Self {
+            project,
+            user: dirs::data_dir().map(|dir| dir.join(DEFAULT_PACKAGES_SUBDIR)),
+            cache: package_cache_directory(),
+            archives: package_archive_directory(),
+        }
+    }
+}
+
+/// Pool of long-lived [`TypstEngine`]s.
+///
+/// Engines are reused for many pages, swapping the main source and page context between
+/// compilations. This way, typst's memoization carries over work from one compilation to the
+/// next.
+pub struct TypstWorlds<'a> {
+    environment: &'a TypstEnvironment,
+    root: PathBuf,
+    directories: PackageDirectories,
+    packages: &'a PackageConfig,
+    /// http agent shared by all engines, so that connections are reused.
+    http: Agent,
+    lock: &'a Lockfile,
+    recorder: &'a Recorder,
+    idle: Mutex<Vec<TypstEngine<'a>>>,
+}
+
+impl<'a> TypstWorlds<'a> {
+    pub(crate) fn new(
+        environment: &'a TypstEnvironment,
+        root: PathBuf,
+        directories: PackageDirectories,
+        packages: &'a PackageConfig,
+        lock: &'a Lockfile,
+        recorder: &'a Recorder,
+    ) -> Self {
+        let http = Agent::config_builder()
+            .timeout_global(Some(packages.timeout()))
+            .proxy(Proxy::try_from_env())
+            .build()
+            .into();
+        Self {
+            environment,
+            root,
+            directories,
+            packages,
+            http,
+            lock,
+            recorder,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Fingerprint of the settings affecting compilation results.
+    ///
+    /// Besides the [`TypstEnvironment`]'s settings, this covers the typst root, as pages address
+    /// their files relative to it.
+    pub fn fingerprint(&self) -> Fingerprint {
+        hash128(&(self.environment.fingerprint(), &self.root))
+    }
+
+    /// Run `f` with an idle engine, setting up a new one if all engines are busy.
+    pub fn with_engine<T>(&self, f: impl FnOnce(&mut TypstEngine<'a>) -> T) -> T {
+        let idle = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+        let mut engine = idle.unwrap_or_else(|| {
+            TypstEngine::new(
+                self.environment,
+                self.root.clone(),
+                self.directories.clone(),
+                self.packages,
+                self.http.clone(),
+                self.lock,
+                self.recorder,
+            )
+        });
+        let result = f(&mut engine);
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(engine);
+        result
+    }
+}
 
-// TODO there is probably quite a lot you can reuse across invocations of typst for different
-// posts.
 /// Main interface that determines the environment for Typst.
-pub struct TypstEngine {
-    /// Root path to which files will be resolved.
+pub struct TypstEngine<'a> {
+    /// Root path to which files will be resolved, shared by all pages.
     root: PathBuf,
 
     /// The content of a source.
     source: Source,
 
-    /// The standard library.
+    /// Filesystem location of `source`.
+    source_path: PathBuf,
+
+    /// The standard library, including the page's `loveletters` bindings.
     library: LazyHash<Library>,
 
-    /// Metadata about all known fonts.
-    book: LazyHash<FontBook>,
+    /// Build-wide state such as fonts.
+    environment: &'a TypstEnvironment,
 
-    /// Metadata about all known fonts.
-    fonts: Vec<FontSlot>,
+    /// Collects package download times.
+    recorder: &'a Recorder,
 
-    /// Map of all known files.
+    /// Map of all files accessed by the current page.
     files: Arc<Mutex<HashMap<FileId, FileEntry>>>,
 
-    /// Cache directory (e.g. where packages are downloaded to).
-    cache_directory: PathBuf,
+    /// Directories packages are looked up in and downloaded to.
+    directories: PackageDirectories,
 
-    /// Project package directory.
-    ///
-    /// Packages from the `loveletters` namespace are loaded from this directory.
-    project_packages_directory: PathBuf,
+    /// How to resolve packages, e.g. whether downloading them is allowed.
+    packages: &'a PackageConfig,
 
     // TODO maybe use `download` from `typst_kit` or `reqwest` instead?
     /// http agent to download packages.
-    http: ureq::Agent,
+    http: Agent,
 
-    // TODO maybe use a different time library for this?
-    /// Datetime.
-    time: time::OffsetDateTime,
+    /// Checksums package archives are verified against.
+    lock: &'a Lockfile,
 }
 
-impl TypstEngine {
-    pub fn new(
-        root_dir: PathBuf,
-        root_file: RelativePath,
-        project_packages_directory: PathBuf,
+impl<'a> TypstEngine<'a> {
+    /// Set up an engine without any page, see [`TypstEngine::prepare`].
+    fn new(
+        environment: &'a TypstEnvironment,
+        root: PathBuf,
+        directories: PackageDirectories,
+        packages: &'a PackageConfig,
+        http: Agent,
+        lock: &'a Lockfile,
+        recorder: &'a Recorder,
+    ) -> Self {
+        Self {
+            library: LazyHash::new(environment.library.clone()),
+            environment,
+            recorder,
+            root,
+            source: Source::detached(""),
+            source_path: PathBuf::new(),
+            directories,
+            packages,
+            http,
+            lock,
+            files: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Swap in the page at `page_dir` for the next compilation.
+    pub fn prepare(
+        &mut self,
+        page_dir: &Path,
+        root_file: &Path,
         gctx: ProjectContext,
         pctx: PageContext,
-    ) -> CrateResult<Self> {
-        let root_file = root_dir.join(root_file);
-        // Top-level content and directory
-        println!("Working on {}", root_file.display());
+    ) -> CrateResult<()> {
+        let root_path = page_dir.join(root_file);
 
-        let root_src = read_to_string(&root_file).map_err(|e| match e.kind() {
+        let root_src = read_to_string(&root_path).map_err(|e| match e.kind() {
             ErrorKind::NotFound => Error::NotFound {
                 missing: EntityKind::TypstRoot,
-                path: root_file,
+                path: root_path.clone(),
             },
             _ => Error::FileIO {
-                path: Some(root_file),
+                path: Some(root_path.clone()),
                 raw: e,
             },
         })?;
 
         // Library
-        let mut lib = Library::builder()
-            .with_features([Feature::Html].into_iter().collect())
-            .build();
-
-        let fonts = FontSearcher::new().include_system_fonts(true).search();
+        let mut lib = self.environment.library.clone();
 
         // Inject loveletters' default top-level bindings
         let mut ctx = Dict::new();
@@ -112,39 +381,92 @@ impl TypstEngine {
         lib.global
             .scope_mut()
             .define("loveletters", ctx.into_value());
+        self.library = LazyHash::new(lib);
+
+        // The page's file is addressed by its location within the root, so that page-relative
+        // imports resolve to the page's directory
+        let virtual_path =
+            root_path
+                .strip_prefix(&self.root)
+                .map_err(|_| Error::InvalidTypstRoot {
+                    path: self.root.clone(),
+                    reason: "it does not contain the content directory",
+                })?;
+
+        // Replacing the text of an existing source lets typst reparse incrementally
+        let id = FileId::new(None, VirtualPath::new(virtual_path));
+        if self.source.id() == id {
+            self.source.replace(&root_src);
+        } else {
+            self.source = Source::new(id, root_src);
+        }
+        self.source_path = root_path;
 
-        Ok(Self {
-            library: LazyHash::new(lib),
-            book: LazyHash::new(fonts.book),
-            root: root_dir,
-            fonts: fonts.fonts,
-            source: Source::detached(root_src),
-            time: time::OffsetDateTime::now_utc(),
-            // TODO set env-dir using proper config handling (e.g. `config` crate)
-            // TODO reuse across instantiations of `TypstEngine` to reduce the number of package downloads
-            cache_directory: var_os("CACHE_DIRECTORY").map_or_else(temp_dir, OsString::into),
-            project_packages_directory,
-            http: agent(),
-            files: Arc::new(Mutex::new(HashMap::new())),
-        })
+        // Only files read by this page are dependencies of this page
+        self.files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+
+        Ok(())
+    }
+}
+
+impl TypstEngine<'_> {
+    /// Human-readable name of the file `id`, e.g. for diagnostics.
+    pub fn file_name(&self, id: FileId) -> String {
+        let vpath = id.vpath();
+        match id.package() {
+            Some(package) => format!("{package}{}", vpath.as_rooted_path().display()),
+            None => vpath
+                .resolve(&self.root)
+                .unwrap_or_else(|| vpath.as_rooted_path().to_path_buf())
+                .display()
+                .to_string(),
+        }
+    }
+
+    /// All files read so far, including the main source.
+    pub fn dependencies(&self) -> Vec<Dependency> {
+        let mut dependencies = vec![Dependency::new(
+            self.source_path.clone(),
+            self.source.text().as_bytes(),
+        )];
+        let files = self.files.lock().unwrap_or_else(PoisonError::into_inner);
+        dependencies.extend(
+            files
+                .values()
+                .filter_map(|file| Some(Dependency::new(file.path.clone()?, &file.bytes))),
+        );
+        dependencies
     }
 }
 
 /// A File that will be stored in the `HashMap`.
 #[derive(Clone, Debug)]
 struct FileEntry {
+    /// Filesystem location the file got read from, `None` for files embedded into the binary.
+    path: Option<PathBuf>,
     bytes: Bytes,
     source: Option<Source>,
 }
 
 impl FileEntry {
-    fn new(bytes: Vec<u8>, source: Option<Source>) -> Self {
+    fn new(path: Option<PathBuf>, bytes: Vec<u8>, source: Option<Source>) -> Self {
         Self {
+            path,
             bytes: Bytes::new(bytes),
             source,
         }
     }
 
+    /// Read the file `id` relative to `root` from disk.
+    fn read(id: FileId, root: &Path) -> FileResult<Self> {
+        let path = id.vpath().resolve(root).ok_or(FileError::AccessDenied)?;
+        let content = read_file(&path).map_err(|error| FileError::from_io(error, &path))?;
+        Ok(Self::new(Some(path), content, None))
+    }
+
     fn source(&mut self, id: FileId) -> FileResult<Source> {
         let source = if let Some(source) = &self.source {
             source
@@ -158,77 +480,241 @@ impl FileEntry {
     }
 }
 
-impl TypstEngine {
+impl TypstEngine<'_> {
     /// Helper to handle file requests.
     ///
     /// Requests will be either in packages or a local file.
-    fn file(&self, id: FileId) -> FileResult<FileEntry> {
+    /// Every file accessed by this page is recorded in the page's own map of files, see
+    /// [`TypstEngine::dependencies`].
+    fn with_file<T>(
+        &self,
+        id: FileId,
+        f: impl FnOnce(&mut FileEntry) -> FileResult<T>,
+    ) -> FileResult<T> {
         let mut files = self.files.lock().map_err(|_| FileError::AccessDenied)?;
-        if let Some(entry) = files.get(&id) {
+        let entry = match files.entry(id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(self.load(id)?),
+        };
+        f(entry)
+    }
+
+    /// Load a file this page has not accessed before.
+    fn load(&self, id: FileId) -> FileResult<FileEntry> {
+        let Some(package) = id.package() else {
+            // Fetching file from disk
+            return FileEntry::read(id, &self.root);
+        };
+
+        // Fetching file from package
+        // Package files are the same for all pages, so they are shared across the whole build
+        let slot = Arc::clone(
+            self.environment
+                .package_files
+                .lock()
+                .map_err(|_| FileError::AccessDenied)?
+                .entry(id)
+                .or_default(),
+        );
+        // The map's lock is released at this point, only the file's own lock is held from here
+        let mut slot = slot.lock().map_err(|_| FileError::AccessDenied)?;
+        if let Some(entry) = &*slot {
             return Ok(entry.clone());
         }
-        let path = if let Some(package) = id.package() {
-            // Fetching file from package
-            let package_dir = self.lookup_or_download_package(package)?;
-            id.vpath().resolve(&package_dir)
+        let mut entry = if let Some(file) = self.lookup_embedded_file(package, id)? {
+            file
         } else {
-            // Fetching file from disk
-            id.vpath().resolve(&self.root)
+            let package_dir = self.lookup_or_download_package(package)?;
+            FileEntry::read(id, &package_dir)?
+        };
+        // Parse sources upfront, so that the parsed source is shared as well
+        if id
+            .vpath()
+            .as_rootless_path()
+            .extension()
+            .is_some_and(|ext| ext == "typ")
+        {
+            let _ = entry.source(id);
         }
-        .ok_or(FileError::AccessDenied)?;
+        Ok(slot.insert(entry).clone())
+    }
 
-        let content = read_file(&path).map_err(|error| FileError::from_io(error, &path))?;
-        Ok(files
-            .entry(id)
-            .or_insert(FileEntry::new(content, None))
-            .clone())
-    }
-
-    /// Lookup packages from the `loveletters` namespace
-    fn lookup_project_package(&self, package: &PackageSpec) -> PackageResult<PathBuf> {
-        if package.name == "loveletters" {
-            // TODO
-            // Currently, we expect the user to copy/link the loveletters package. At a later
-            // point, we will want to provide it ourselves.
+    /// Lookup the file `id` of the `@loveletters/loveletters` package embedded into the binary.
+    ///
+    /// Returns `None` for all other packages as well as when the project provides its own copy of
+    /// the requested version, which takes precedence.
+    fn lookup_embedded_file(
+        &self,
+        package: &PackageSpec,
+        id: FileId,
+    ) -> FileResult<Option<FileEntry>> {
+        if package.namespace != "loveletters" || package.name != "loveletters" {
+            return Ok(None);
         }
-
-        let package_dir = self.project_packages_directory.join(package.name.as_str());
-        if !package_dir.exists() {
-            return Err(PackageError::NotFound(package.clone()));
+        if self.lookup_project_package(package).is_some() {
+            return Ok(None);
         }
+        let version = package.version.to_string();
+
+        let Some((_, files)) = EMBEDDED_UTIL_PACKAGES
+            .iter()
+            .find(|(embedded, _)| *embedded == version)
+        else {
+            let available: Vec<_> = EMBEDDED_UTIL_PACKAGES
+                .iter()
+                .map(|(embedded, _)| *embedded)
+                .collect();
+            return Err(PackageError::Other(Some(eco_format!(
+                "{package} is not provided by this version of loveletters (available versions: {})",
+                available.join(", ")
+            )))
+            .into());
+        };
 
-        let version_dir = package_dir.join(format!("{}", package.version));
-        if !version_dir.exists() {
-            return Err(PackageError::VersionNotFound(
-                package.clone(),
-                package.version,
-            ));
-        }
+        let path = id.vpath().as_rootless_path();
+        let (_, content) = files
+            .iter()
+            .find(|(name, _)| Path::new(name) == path)
+            .ok_or_else(|| FileError::NotFound(path.to_path_buf()))?;
+        Ok(Some(FileEntry::new(None, content.to_vec(), None)))
+    }
+
+    /// Lookup `package` in the project package directory.
+    ///
+    /// Packages are located at `<namespace>/<name>/<version>`. For the `loveletters` namespace,
+    /// `<name>/<version>` is supported as well.
+    fn lookup_project_package(&self, package: &PackageSpec) -> Option<PathBuf> {
+        let version = package.version.to_string();
+        let vendored = package_subdir(&self.directories.project, package);
+        let legacy = (package.namespace == "loveletters").then(|| {
+            self.directories
+                .project
+                .join(package.name.as_str())
+                .join(&version)
+        });
+        [Some(vendored), legacy]
+            .into_iter()
+            .flatten()
+            .find(|dir| dir.is_dir())
+    }
 
-        Ok(version_dir)
+    /// Lookup `package` among the packages installed by the user, e.g. for the typst CLI.
+    fn lookup_user_package(&self, package: &PackageSpec) -> Option<PathBuf> {
+        self.directories
+            .user
+            .as_ref()
+            .map(|dir| package_subdir(dir, package))
+            .filter(|dir| dir.is_dir())
     }
 
-    /// Downloads the package and returns the system path of the unpacked package.
+    /// Returns the system path of the unpacked package, downloading the package if necessary.
+    ///
+    /// Packages vendored into the project take precedence over packages installed by the user,
+    /// which in turn take precedence over downloaded ones. Packages from the `loveletters`
+    /// namespace are never looked up outside the project.
     fn lookup_or_download_package(&self, package: &PackageSpec) -> PackageResult<PathBuf> {
-        if package.namespace == "loveletters" {
-            return self.lookup_project_package(package);
+        if let Some(path) = self
+            .environment
+            .package_dirs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(package)
+        {
+            return Ok(path.clone());
         }
 
-        let package_subdir = format!("{}/{}/{}", package.namespace, package.name, package.version);
+        let path = match self.lookup_project_package(package) {
+            Some(path) => path,
+            None if package.namespace == "loveletters" => {
+                return Err(PackageError::NotFound(package.clone()));
+            }
+            None => match self.lookup_user_package(package) {
+                Some(path) => path,
+                None => self.download_package(package)?,
+            },
+        };
+        self.environment
+            .package_dirs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(package.clone(), path.clone());
+        self.recorder.record_package(ResolvedPackage {
+            namespace: package.namespace.to_string(),
+            name: package.name.to_string(),
+            version: package.version.to_string(),
+            path: path.clone(),
+        });
+        Ok(path)
+    }
+
+    /// Verify the archive of `package` against the lockfile.
+    fn verify_package(&self, package: &PackageSpec, archive: &[u8]) -> PackageResult<()> {
+        self.lock
+            .verify(&package.to_string(), archive)
+            .map_err(|error| PackageError::Other(Some(error.into())))
+    }
 
-        let path = self.cache_directory.join(package_subdir);
+    fn download_package(&self, package: &PackageSpec) -> PackageResult<PathBuf> {
+        let path = package_subdir(&self.directories.cache, package);
+        // Archives are kept, so that a reused package can be verified as well
+        let archive_entry = package_subdir(&self.directories.archives, package);
+        let archive_path = archive_entry.with_file_name(format!("{}.tar.gz", package.version));
+
+        // Only namespaces with a registry can be downloaded, e.g. `@local` packages cannot
+        let url = self.packages.archive_url(
+            &package.namespace,
+            &package.name,
+            &package.version.to_string(),
+        );
 
-        if path.exists() {
+        // A package never fetched before cannot be fetched now either, which is reported before
+        // touching the cache
+        if !path.join(MANIFEST_FILENAME).is_file() {
+            if url.is_none() {
+                return Err(PackageError::NotFound(package.clone()));
+            }
+            if self.packages.offline() {
+                return Err(offline_error(
+                    package,
+                    &path,
+                    "is not available offline; build once without offline mode to download it",
+                ));
+            }
+        }
+
+        // Other builds may populate the cache concurrently, so the entry is locked until the
+        // package is complete
+        let cache_lock = lock_cache_entry(&archive_entry)
+            .map_err(|error| PackageError::Other(Some(eco_format!("{error}"))))?;
+        // The lock's modification time tells when the package got last used
+        let _ = cache_lock.set_modified(SystemTime::now());
+
+        // A package without manifest is a leftover of a broken download, which gets refetched
+        let complete = path.join(MANIFEST_FILENAME).is_file();
+        // A package unpacked by another tool lacks an archive, which is downloaded below to verify
+        // (or record) the package
+        if complete && let Ok(compressed_archive) = read_file(&archive_path) {
+            self.verify_package(package, &compressed_archive)?;
+            ensure_unpacked(&compressed_archive, &path)?;
             return Ok(path);
         }
 
-        eprintln!("downloading {package}");
-        let url = format!(
-            "https://packages.typst.org/{}/{}-{}.tar.gz",
-            package.namespace, package.name, package.version,
-        );
+        let Some(url) = url else {
+            return Err(PackageError::NotFound(package.clone()));
+        };
 
-        let mut response = retry(|| {
+        if self.packages.offline() {
+            let reason = if complete {
+                "cannot be verified offline, as its archive is missing; build once without offline mode to download the archive"
+            } else {
+                "is not available offline; build once without offline mode to download it"
+            };
+            return Err(offline_error(package, &path, reason));
+        }
+
+        eprintln!("downloading {package}");
+        let start = Instant::now();
+        let mut response = retry(self.packages.retries(), || {
             let response = self
                 .http
                 .get(&url)
@@ -252,16 +738,14 @@ impl TypstEngine {
             .as_reader()
             .read_to_end(&mut compressed_archive)
             .map_err(|error| PackageError::NetworkFailed(Some(eco_format!("{error}"))))?;
-        // TODO maybe look into alternatives to zune_inflate?
-        let raw_archive = zune_inflate::DeflateDecoder::new(&compressed_archive)
-            .decode_gzip()
-            .map_err(|error| PackageError::MalformedArchive(Some(eco_format!("{error}"))))?;
-        let mut archive = tar::Archive::new(raw_archive.as_slice());
-        archive.unpack(&path).map_err(|error| {
-            _ = remove_dir_all(&path);
-            PackageError::MalformedArchive(Some(eco_format!("{error}")))
-        })?;
-
+        self.verify_package(package, &compressed_archive)?;
+        write_atomically(&archive_path, &compressed_archive)
+            .map_err(|error| PackageError::Other(Some(eco_format!("{error}"))))?;
+        // A package unpacked by another tool is left alone, as long as it matches the archive
+        ensure_unpacked(&compressed_archive, &path)?;
+
+        self.recorder
+            .record_download(package.to_string(), start.elapsed());
         Ok(path)
     }
 }
@@ -269,7 +753,7 @@ impl TypstEngine {
 /// This is the interface we have to implement such that `typst` can compile it.
 ///
 /// I have tried to keep it as minimal as possible
-impl typst::World for TypstEngine {
+impl typst::World for TypstEngine<'_> {
     /// Standard library.
     fn library(&self) -> &LazyHash<Library> {
         &self.library
@@ -277,7 +761,7 @@ impl typst::World for TypstEngine {
 
     /// Metadata about all known Books.
     fn book(&self) -> &LazyHash<FontBook> {
-        &self.book
+        &self.environment.book
     }
 
     /// Accessing the main source file.
@@ -290,18 +774,18 @@ impl typst::World for TypstEngine {
         if id == self.source.id() {
             Ok(self.source.clone())
         } else {
-            self.file(id)?.source(id)
+            self.with_file(id, |file| file.source(id))
         }
     }
 
     /// Accessing a specified file (non-file).
     fn file(&self, id: FileId) -> FileResult<Bytes> {
-        self.file(id).map(|file| file.bytes.clone())
+        self.with_file(id, |file| Ok(file.bytes.clone()))
     }
 
     /// Accessing a specified font per index of font book.
     fn font(&self, id: usize) -> Option<Font> {
-        self.fonts[id].get()
+        self.environment.fonts[id].get()
     }
 
     /// Get the current date.
@@ -310,13 +794,130 @@ impl typst::World for TypstEngine {
     fn today(&self, offset: Option<i64>) -> Option<Datetime> {
         let offset = offset.unwrap_or(0);
         let offset = time::UtcOffset::from_hms(offset.try_into().ok()?, 0, 0).ok()?;
-        let time = self.time.checked_to_offset(offset)?;
+        let time = self.environment.timestamp.checked_to_offset(offset)?;
         Some(Datetime::Date(time.date()))
     }
 }
 
-fn retry<T, E>(mut f: impl FnMut() -> Result<T, E>) -> Result<T, E> {
-    if let Ok(ok) = f() { Ok(ok) } else { f() }
+/// Lock the cache entry at `path` against concurrent builds, until the returned file is dropped.
+///
+/// The lock is held on a sibling `<version>.lock` file, as `path` itself may not exist yet. Cache
+/// entries are locked in the archive directory, which is owned by `loveletters` alone.
+pub(crate) fn lock_cache_entry(path: &Path) -> CrateResult<File> {
+    let mut lock_name = path.file_name().unwrap_or_default().to_owned();
+    lock_name.push(".lock");
+    let lock_path = path.with_file_name(lock_name);
+    let to_error = |e| Error::FileIO {
+        path: Some(lock_path.clone()),
+        raw: e,
+    };
+    if let Some(parent) = lock_path.parent() {
+        create_dir_all(parent).map_err(to_error)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(to_error)?;
+    file.lock().map_err(to_error)?;
+    Ok(file)
+}
+
+/// Make sure the package at `path` consists of the files in the gzipped tarball
+/// `compressed_archive`.
+///
+/// Typst reads the unpacked package rather than its (verified) archive. Hence, a package whose
+/// files differ from the archive, e.g. as they got edited or only partially unpacked, is unpacked
+/// anew.
+fn ensure_unpacked(compressed_archive: &[u8], path: &Path) -> PackageResult<()> {
+    // TODO maybe look into alternatives to zune_inflate?
+    let raw_archive = zune_inflate::DeflateDecoder::new(compressed_archive)
+        .decode_gzip()
+        .map_err(|error| PackageError::MalformedArchive(Some(eco_format!("{error}"))))?;
+    if !is_unpacked(&raw_archive, path) {
+        unpack_package(&raw_archive, path)?;
+    }
+    Ok(())
+}
+
+/// Whether every file of the tarball `raw_archive` is present at `path` with the same content.
+fn is_unpacked(raw_archive: &[u8], path: &Path) -> bool {
+    let mut archive = tar::Archive::new(raw_archive);
+    let Ok(entries) = archive.entries() else {
+        return false;
+    };
+    for entry in entries {
+        let Ok(mut entry) = entry else {
+            return false;
+        };
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let Ok(file) = entry.path().map(|file| path.join(file)) else {
+            return false;
+        };
+        let mut expected = Vec::new();
+        if entry.read_to_end(&mut expected).is_err()
+            || read_file(&file).ok().as_ref() != Some(&expected)
+        {
+            return false;
+        }
+    }
+    true
+}
+
+/// Unpack the tarball `raw_archive` to `path`, replacing a broken previous package.
+///
+/// The package is unpacked to a [`temporary_sibling`] first.
+fn unpack_package(raw_archive: &[u8], path: &Path) -> PackageResult<()> {
+    let temp_path = temporary_sibling(path);
+    // Leftover of a previous, interrupted unpack
+    let _ = remove_dir_all(&temp_path);
+
+    tar::Archive::new(raw_archive)
+        .unpack(&temp_path)
+        .map_err(|error| {
+            let _ = remove_dir_all(&temp_path);
+            PackageError::MalformedArchive(Some(eco_format!("{error}")))
+        })?;
+    if path.exists() {
+        let _ = remove_dir_all(path);
+    }
+    rename(&temp_path, path).map_err(|error| {
+        let _ = remove_dir_all(&temp_path);
+        PackageError::Other(Some(eco_format!(
+            "failed to move package into '{}': {error}",
+            path.display()
+        )))
+    })
+}
+
+/// Error for `package`, which cannot be used offline for `reason`.
+fn offline_error(package: &PackageSpec, path: &Path, reason: &str) -> PackageError {
+    PackageError::Other(Some(eco_format!(
+        "{package} {reason}, run `loveletters vendor` to vendor it into the project, or copy the package to '{}'",
+        path.display()
+    )))
+}
+
+/// Directory of `package` below `dir`, at `<namespace>/<name>/<version>`.
+fn package_subdir(dir: &Path, package: &PackageSpec) -> PathBuf {
+    dir.join(package.namespace.as_str())
+        .join(package.name.as_str())
+        .join(package.version.to_string())
+}
+
+/// Run `f`, retrying it up to `retries` times on failure.
+fn retry<T, E>(retries: u32, mut f: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    let mut result = f();
+    for _ in 0..retries {
+        if result.is_ok() {
+            break;
+        }
+        result = f();
+    }
+    result
 }
 
 fn http_successful(status: u16) -> bool {
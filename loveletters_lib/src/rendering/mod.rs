@@ -2,40 +2,101 @@ pub mod context;
 mod driver_typst;
 
 use crate::{
-    bundleing::{InMemFile, PageBundle},
+    bundleing::{Bundler, InMemFile, PageBundle},
+    cache::{BuildCache, Fingerprint},
     content::{IndexFrontmatter, LeafFrontmatter},
     error::{Error, Result},
     frontmatter_parsing::PageWithFrontmatter,
+    language::{Language, Translations},
     page::{Index, Leaf, Mode},
     rendering::context::{PageContext, ProjectContext},
+    report::{Phase, Recorder},
     section::Section,
+    selection::Selection,
 };
-use driver_typst::TypstEngine;
+use bytes::Bytes;
+use codespan_reporting::{
+    diagnostic::{Diagnostic, Label},
+    files::SimpleFiles,
+    term,
+};
+pub(crate) use driver_typst::lock_cache_entry;
+pub use driver_typst::{
+    PackageDirectories, TypstEnvironment, TypstWorlds, evict_memoized, package_archive_directory,
+    package_cache_directory,
+};
+use driver_typst::{RelativePath, TypstEngine};
 use std::{
+    collections::{HashMap, hash_map::Entry},
     error,
     fmt::{self, Debug, Display},
+    io::{self, IsTerminal},
     marker::PhantomData,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
+use termcolor::Buffer;
 use typst::{
+    World, WorldExt,
     diag::{Severity, SourceDiagnostic},
-    ecow::EcoVec,
+    foundations::IntoValue,
+    syntax::Span,
+    utils::hash128,
 };
 use typst_html::HtmlDocument;
 
 struct TypstError {
-    diagnostics: EcoVec<SourceDiagnostic>,
+    /// Sources of all files referenced by `diagnostics`.
+    files: SimpleFiles<String, String>,
+    /// Diagnostics along with their traces, which are reported like separate diagnostics.
+    diagnostics: Vec<Diagnostic<usize>>,
 }
 
 impl TypstError {
-    fn from_diagnostics(diagnostics: EcoVec<SourceDiagnostic>) -> Self {
-        Self { diagnostics }
-    }
-}
+    /// Resolve the spans of `diagnostics` through `engine`, so that they can be reported once
+    /// `engine` is gone.
+    fn new(diagnostics: &[SourceDiagnostic], engine: &TypstEngine<'_>) -> Self {
+        let mut files = SimpleFiles::new();
+        let mut file_ids = HashMap::new();
+        let mut label = |span: Span| -> Option<Label<usize>> {
+            let id = span.id()?;
+            let file = match file_ids.entry(id) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    let source = engine.source(id).ok()?;
+                    *entry.insert(files.add(engine.file_name(id), source.text().to_owned()))
+                }
+            };
+            Some(Label::primary(file, engine.range(span)?))
+        };
 
-impl From<EcoVec<SourceDiagnostic>> for TypstError {
-    fn from(value: EcoVec<SourceDiagnostic>) -> Self {
-        Self::from_diagnostics(value)
+        // Mirrors how the typst CLI reports diagnostics, see
+        // https://github.com/typst/typst/blob/v0.14.0/crates/typst-cli/src/compile.rs
+        let mut reported = Vec::new();
+        for d in diagnostics {
+            let diagnostic = match d.severity {
+                Severity::Error => Diagnostic::error(),
+                Severity::Warning => Diagnostic::warning(),
+            };
+            reported.push(
+                diagnostic
+                    .with_message(d.message.as_str())
+                    .with_notes(d.hints.iter().map(|h| format!("hint: {h}")).collect())
+                    .with_labels(label(d.span).into_iter().collect()),
+            );
+            // Stacktrace-like helper diagnostics
+            for point in &d.trace {
+                reported.push(
+                    Diagnostic::help()
+                        .with_message(point.v.to_string())
+                        .with_labels(label(point.span).into_iter().collect()),
+                );
+            }
+        }
+
+        Self {
+            files,
+            diagnostics: reported,
+        }
     }
 }
 
@@ -45,33 +106,30 @@ impl Display for TypstError {
     }
 }
 
-fn describe_severity(severity: Severity) -> &'static str {
-    match severity {
-        Severity::Error => "error",
-        Severity::Warning => "warning",
-    }
-}
-
 impl Debug for TypstError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Here is how typst prints out messages internally (for a HintedStrResult -> HintedString):
-        // https://github.com/typst/typst/blob/bf946178ec3cb34c06b4666ea237a025b4ca4aa0/crates/typst-cli/src/main.rs#L59-L65
-        // https://docs.rs/typst/latest/typst/diag/type.HintedStrResult.html
-
         if self.diagnostics.is_empty() {
-            writeln!(
+            return writeln!(
                 f,
                 "failed to compile document without further diagnostics to show"
-            )?;
+            );
+        }
+
+        // Errors end up on stderr, which is not necessarily a terminal understanding colours
+        let mut buffer = if io::stderr().is_terminal() {
+            Buffer::ansi()
         } else {
-            for d in &self.diagnostics {
-                write!(f, "\n    {}: {}", describe_severity(d.severity), d.message)?;
-                for h in &d.hints {
-                    write!(f, "\n        > {h}")?;
-                }
-            }
+            Buffer::no_color()
+        };
+        let config = term::Config {
+            tab_width: 2,
+            ..term::Config::default()
+        };
+        for diagnostic in &self.diagnostics {
+            term::emit_to_write_style(&mut buffer, &config, &self.files, diagnostic)
+                .map_err(|_| fmt::Error)?;
         }
-        Ok(())
+        write!(f, "\n{}", String::from_utf8_lossy(buffer.as_slice()))
     }
 }
 
@@ -81,92 +139,177 @@ impl error::Error for TypstError {
     }
 }
 
+/// A page serialized to HTML.
 pub struct RenderedPage<M> {
-    content_dir: PathBuf,
-    rendering: HtmlDocument,
+    /// The page's typst file, see [`Mode::typst_file`].
+    page: PathBuf,
+    html: Bytes,
     m: PhantomData<M>,
 }
 
 impl<M> RenderedPage<M> {
-    pub fn new(content_dir: PathBuf, rendering: HtmlDocument) -> Self {
+    pub fn new(page: PathBuf, html: Bytes) -> Self {
         Self {
-            content_dir,
-            rendering,
+            page,
+            html,
             m: PhantomData,
         }
     }
 
-    pub fn try_bundle(self, output_dir: PathBuf) -> Result<PageBundle> {
-        let html = typst_html::html(&self.rendering).map_err(|e| {
-            let err: TypstError = e.into();
-            Error::Compilation {
-                page: self.content_dir,
-                raw: err.into(),
-            }
-        })?;
-        Ok(PageBundle::new(output_dir, InMemFile::new(html.into())))
+    pub fn page(&self) -> &Path {
+        &self.page
+    }
+
+    /// Size of the serialized page in bytes.
+    pub fn size(&self) -> usize {
+        self.html.len()
+    }
+
+    pub fn bundle(self, output_dir: PathBuf) -> PageBundle {
+        PageBundle::new(output_dir, InMemFile::new(self.html))
     }
 }
 
-pub struct Renderer {
+pub struct Renderer<'a> {
     ctx: ProjectContext,
-    project_packages: PathBuf,
+    /// Fingerprint of `ctx`, computed once as `ctx` is shared by all pages.
+    ///
+    /// This deliberately covers all of `ctx` rather than the parts a page actually accesses: any
+    /// page may list any other page's frontmatter, and tracking accesses would require hooking
+    /// into typst's evaluation. Hence, changing some page's frontmatter (not its content) renders
+    /// all pages again.
+    ctx_fingerprint: Fingerprint,
+    worlds: &'a TypstWorlds<'a>,
+    translations: &'a Translations,
+    cache: &'a BuildCache,
+    recorder: &'a Recorder,
+    selection: &'a Selection,
 }
 
-impl Renderer {
-    pub fn new(ctx: ProjectContext, project_packages_dir: PathBuf) -> Self {
+impl<'a> Renderer<'a> {
+    pub(crate) fn new(
+        ctx: ProjectContext,
+        worlds: &'a TypstWorlds<'a>,
+        translations: &'a Translations,
+        cache: &'a BuildCache,
+        recorder: &'a Recorder,
+        selection: &'a Selection,
+    ) -> Self {
         Self {
+            ctx_fingerprint: hash128(&ctx.clone().into_value()),
             ctx,
-            project_packages: project_packages_dir,
+            worlds,
+            translations,
+            cache,
+            recorder,
+            selection,
         }
     }
 
+    /// Render all pages of `content`, handing each page to `bundler` as soon as it is rendered.
     pub fn try_render(
         &self,
         content: Section<
             PageWithFrontmatter<Index, IndexFrontmatter>,
             PageWithFrontmatter<Leaf, LeafFrontmatter>,
         >,
-    ) -> Result<Section<RenderedPage<Index>, RenderedPage<Leaf>>> {
-        content.try_walk(
+        bundler: &Bundler,
+    ) -> Result<()> {
+        let rendered = content.try_par_walk(
             |path, page| {
-                let ctx = PageContext::new(path, None);
-                page.try_render(self, ctx)
+                let ctx = PageContext::new(path, None, self.translations);
+                page.try_render(self, ctx)?
+                    .map(|rendering| bundler.try_bundle_page(path, None, rendering))
+                    .transpose()
             },
             |path, slug, page| {
-                let ctx = PageContext::new(path, Some(slug));
-                page.try_render(self, ctx)
+                let ctx = PageContext::new(path, Some(slug), self.translations);
+                page.try_render(self, ctx)?
+                    .map(|rendering| bundler.try_bundle_page(path, Some(slug), rendering))
+                    .transpose()
             },
-        )
+        )?;
+
+        // Pages finish in arbitrary order, so report them once all are done and in a stable order
+        let mut index_files = Vec::new();
+        let mut leaf_files = Vec::new();
+        rendered.for_each(
+            &mut |_, page| {
+                index_files.extend(page.clone());
+            },
+            &mut |_, _, page| {
+                leaf_files.extend(page.clone());
+            },
+        );
+        let mut pages: Vec<_> = index_files.into_iter().chain(leaf_files).collect();
+        pages.sort();
+        for page in pages {
+            println!("Rendered {}", page.display());
+        }
+
+        Ok(())
     }
 
     pub fn try_render_dir<M>(
         &self,
-        content_dir: PathBuf,
+        content_dir: &Path,
+        suffix: Option<&Language>,
         page_ctx: PageContext,
-    ) -> Result<RenderedPage<M>>
+    ) -> Result<Option<RenderedPage<M>>>
     where
         M: Mode,
     {
-        // TODO: should probably be something like
-        // let engine = TypstEngine::new();
-        // let entrypoint = engine.wrap(&self);
-        // or similar...
-        let root_file = M::typst_filename().into();
-        let entrypoint = TypstEngine::new(
-            content_dir.clone(),
-            root_file,
-            self.project_packages.clone(),
-            self.ctx.clone(),
-            page_ctx,
+        let root_file: RelativePath = M::typst_filename(suffix).into();
+        let page = M::typst_file(content_dir, suffix);
+
+        let cache_key = page.display().to_string();
+
+        let url = self.translations.url(
+            self.ctx.language(),
+            page_ctx.section_path(),
+            page_ctx.page(),
         );
-        let typst_document = typst::compile(&entrypoint?).output.map_err(|e| {
-            let err: TypstError = e.into();
-            Error::Compilation {
-                page: content_dir.clone(),
-                raw: err.into(),
-            }
+        if !self.selection.contains(content_dir, &page, &url) {
+            // Keep the page's cache entry for builds rendering it again
+            self.cache.retain(&cache_key);
+            return Ok(None);
+        }
+
+        // Everything the page depends on apart from the files read during compilation, which are
+        // tracked by the cache itself
+        let fingerprint = hash128(&(
+            self.ctx_fingerprint,
+            page_ctx.into_value(),
+            self.worlds.fingerprint(),
+        ));
+        if let Some(html) = self.cache.lookup(&cache_key, fingerprint) {
+            self.recorder.record_cache_hit(&page);
+            return Ok(Some(RenderedPage::new(page, html)));
+        }
+
+        let (html, dependencies) = self.worlds.with_engine(|engine| -> Result<_> {
+            engine.prepare(content_dir, &root_file, self.ctx.clone(), page_ctx)?;
+            let typst_document = self
+                .recorder
+                .time(&page, Phase::Compile, || {
+                    typst::compile::<HtmlDocument>(&*engine).output
+                })
+                .map_err(|e| Error::Compilation {
+                    page: content_dir.to_path_buf(),
+                    raw: TypstError::new(&e, engine).into(),
+                })?;
+            let html = self
+                .recorder
+                .time(&page, Phase::Html, || typst_html::html(&typst_document))
+                .map_err(|e| Error::Compilation {
+                    page: content_dir.to_path_buf(),
+                    raw: TypstError::new(&e, engine).into(),
+                })?;
+            Ok((html, engine.dependencies()))
         })?;
-        Ok(RenderedPage::new(content_dir, typst_document))
+
+        self.cache
+            .store(&cache_key, fingerprint, dependencies, html.as_bytes())?;
+        Ok(Some(RenderedPage::new(page, html.into())))
     }
 }
@@ -0,0 +1,295 @@
+//! Statistics about a finished build.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display},
+    fs,
+    io::Error as IoError,
+    path::{Path, PathBuf},
+    result,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, Serializer};
+
+use crate::error::{Error, Result};
+
+/// Serialize a [`Duration`] as fractional milliseconds.
+fn as_millis<S: Serializer>(duration: &Duration, serializer: S) -> result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(millis(*duration))
+}
+
+/// A step in processing a single page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Locating the page in the content directory.
+    Discovery,
+    /// Reading and parsing the page's frontmatter.
+    Frontmatter,
+    /// Compiling the page with typst.
+    Compile,
+    /// Serializing the compiled page to HTML.
+    Html,
+    /// Writing the page to the output directory.
+    Bundling,
+}
+
+/// Time spent in each [`Phase`] of a page, serialized in milliseconds.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PhaseTimings {
+    /// See [`Phase::Discovery`].
+    #[serde(serialize_with = "as_millis")]
+    pub discovery: Duration,
+    /// See [`Phase::Frontmatter`].
+    #[serde(serialize_with = "as_millis")]
+    pub frontmatter: Duration,
+    /// See [`Phase::Compile`].
+    #[serde(serialize_with = "as_millis")]
+    pub compile: Duration,
+    /// See [`Phase::Html`].
+    #[serde(serialize_with = "as_millis")]
+    pub html: Duration,
+    /// See [`Phase::Bundling`].
+    #[serde(serialize_with = "as_millis")]
+    pub bundling: Duration,
+}
+
+impl PhaseTimings {
+    fn get_mut(&mut self, phase: Phase) -> &mut Duration {
+        match phase {
+            Phase::Discovery => &mut self.discovery,
+            Phase::Frontmatter => &mut self.frontmatter,
+            Phase::Compile => &mut self.compile,
+            Phase::Html => &mut self.html,
+            Phase::Bundling => &mut self.bundling,
+        }
+    }
+
+    /// Time spent on the page across all phases.
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.discovery + self.frontmatter + self.compile + self.html + self.bundling
+    }
+}
+
+/// Statistics about a single page.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PageReport {
+    /// The page's typst file.
+    pub page: PathBuf,
+    /// Whether the page got written to the output directory, be it compiled or reused from the
+    /// build cache.
+    pub rendered: bool,
+    /// Whether the page got reused from the build cache instead of being compiled.
+    pub cached: bool,
+    /// Size of the page's output in bytes.
+    pub output_bytes: u64,
+    /// Time spent on the page.
+    pub timings: PhaseTimings,
+}
+
+/// A package downloaded during the build.
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadReport {
+    /// The package's specification, e.g. `@preview/example:0.1.0`.
+    pub package: String,
+    /// Time spent downloading and unpacking the package.
+    #[serde(serialize_with = "as_millis")]
+    pub duration: Duration,
+}
+
+/// A package resolved during the build.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedPackage {
+    /// The package's namespace, e.g. `preview`.
+    pub namespace: String,
+    /// The package's name.
+    pub name: String,
+    /// The package's version, e.g. `0.1.0`.
+    pub version: String,
+    /// Directory the package got read from.
+    pub path: PathBuf,
+}
+
+impl Display for ResolvedPackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}/{}:{}", self.namespace, self.name, self.version)
+    }
+}
+
+/// Statistics about a finished build, as returned by [`crate::render_dir`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BuildReport {
+    /// Number of pages written to the output directory across all languages.
+    pub page_count: usize,
+    /// Number of pages reused from the build cache.
+    pub cache_hits: usize,
+    /// Size of all pages' output in bytes.
+    pub output_bytes: u64,
+    /// Statistics per page, slowest page first.
+    pub pages: Vec<PageReport>,
+    /// Packages downloaded during the build, slowest download first.
+    pub downloads: Vec<DownloadReport>,
+    /// Packages used during the build (apart from embedded ones), ordered by specification.
+    pub packages: Vec<ResolvedPackage>,
+}
+
+impl BuildReport {
+    /// Write this report to `path` as JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] when the report cannot be written.
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| Error::FileIO {
+            path: Some(path.to_path_buf()),
+            raw: IoError::other(e),
+        })?;
+        fs::write(path, json).map_err(|e| Error::FileIO {
+            path: Some(path.to_path_buf()),
+            raw: e,
+        })
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Human-readable summary, listing the slowest pages first.
+impl Display for BuildReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>10} {:>10} {:>10} {:>10} {:>10} {:>10}  page",
+            "total ms", "discovery", "frontmatter", "compile", "html", "bundling"
+        )?;
+        for page in &self.pages {
+            let t = &page.timings;
+            write!(
+                f,
+                "{:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>10.1}  {}",
+                millis(t.total()),
+                millis(t.discovery),
+                millis(t.frontmatter),
+                millis(t.compile),
+                millis(t.html),
+                millis(t.bundling),
+                page.page.display(),
+            )?;
+            if !page.rendered {
+                write!(f, " (skipped)")?;
+            } else if page.cached {
+                write!(f, " (cached)")?;
+            }
+            writeln!(f)?;
+        }
+        for download in &self.downloads {
+            writeln!(
+                f,
+                "{:>10.1}  downloading {}",
+                millis(download.duration),
+                download.package
+            )?;
+        }
+        write!(
+            f,
+            "{} pages ({} cached), {} bytes of output",
+            self.page_count, self.cache_hits, self.output_bytes
+        )
+    }
+}
+
+/// Collects statistics while pages are processed concurrently.
+#[derive(Default)]
+pub(crate) struct Recorder {
+    pages: Mutex<HashMap<PathBuf, PageReport>>,
+    downloads: Mutex<Vec<DownloadReport>>,
+    packages: Mutex<BTreeMap<String, ResolvedPackage>>,
+}
+
+impl Recorder {
+    fn with_page(&self, page: &Path, f: impl FnOnce(&mut PageReport)) {
+        let mut pages = self.pages.lock().unwrap_or_else(PoisonError::into_inner);
+        let report = pages
+            .entry(page.to_path_buf())
+            .or_insert_with(|| PageReport {
+                page: page.to_path_buf(),
+                ..PageReport::default()
+            });
+        f(report);
+    }
+
+    /// Run `f`, attributing the time spent to `phase` of `page`.
+    pub fn time<T>(&self, page: &Path, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(page, phase, start.elapsed());
+        result
+    }
+
+    pub fn record(&self, page: &Path, phase: Phase, duration: Duration) {
+        self.with_page(page, |report| *report.timings.get_mut(phase) += duration);
+    }
+
+    pub fn record_cache_hit(&self, page: &Path) {
+        self.with_page(page, |report| report.cached = true);
+    }
+
+    pub fn record_output(&self, page: &Path, bytes: u64) {
+        self.with_page(page, |report| {
+            report.rendered = true;
+            report.output_bytes += bytes;
+        });
+    }
+
+    pub fn record_download(&self, package: String, duration: Duration) {
+        self.downloads
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(DownloadReport { package, duration });
+    }
+
+    pub fn record_package(&self, package: ResolvedPackage) {
+        self.packages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(package.to_string(), package);
+    }
+
+    pub fn finish(self) -> BuildReport {
+        let mut pages: Vec<_> = self
+            .pages
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .into_values()
+            .collect();
+        pages.sort_by(|a, b| {
+            b.timings
+                .total()
+                .cmp(&a.timings.total())
+                .then_with(|| a.page.cmp(&b.page))
+        });
+        let mut downloads = self
+            .downloads
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        downloads.sort_by_key(|download| Reverse(download.duration));
+
+        BuildReport {
+            page_count: pages.iter().filter(|page| page.rendered).count(),
+            cache_hits: pages.iter().filter(|page| page.cached).count(),
+            output_bytes: pages.iter().map(|page| page.output_bytes).sum(),
+            pages,
+            downloads,
+            packages: self
+                .packages
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner)
+                .into_values()
+                .collect(),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use typst::foundations::{Dict, IntoValue, Value};
 
 use crate::{error::Result, slug::Slug};
@@ -29,7 +30,7 @@ impl<I, L> Section<I, L> {
 
     /// Map this section.
     ///
-    /// If you need access to a [`Section`]'s fully qualified path, see [`Section::try_walk`] instead.
+    /// If you need access to a [`Section`]'s fully qualified path, see [`Section::try_par_walk`] instead.
     pub fn try_map<J, M, FIndex, FLeaf>(
         mut self,
         f_index: FIndex,
@@ -64,49 +65,73 @@ impl<I, L> Section<I, L> {
         })
     }
 
-    #[allow(
-        clippy::needless_pass_by_value,
-        reason = "path is cloned multiple times inside this function so do not pretend we do not need ownership"
-    )]
-    fn try_walk_helper<J, M, FIndex, FLeaf>(
-        mut self,
-        path: Vec<Slug>,
-        f_index: FIndex,
-        f_leaf: FLeaf,
+    fn try_par_walk_helper<J, M, FIndex, FLeaf>(
+        self,
+        path: &[Slug],
+        f_index: &FIndex,
+        f_leaf: &FLeaf,
     ) -> Result<Section<J, M>>
     where
-        FIndex: Fn(&[Slug], I) -> Result<J> + Clone, // Clone to prevent recursive type
-        FLeaf: Fn(&[Slug], &Slug, L) -> Result<M> + Clone,
+        I: Send,
+        L: Send,
+        J: Send,
+        M: Send,
+        FIndex: Fn(&[Slug], I) -> Result<J> + Sync,
+        FLeaf: Fn(&[Slug], &Slug, L) -> Result<M> + Sync,
     {
-        let new_index = f_index(&path, self.index)?;
+        let Self {
+            slug,
+            index,
+            pages,
+            sub_sections,
+        } = self;
 
-        let new_leafs = self
-            .pages
-            .drain()
-            .map(|(slug, page)| {
-                let new_leaf = f_leaf(&path, &slug, page);
-                Ok((slug, new_leaf?))
-            })
+        let mut pages: Vec<_> = pages.into_iter().collect();
+        pages.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut sub_sections: Vec<_> = sub_sections.into_iter().collect();
+        sub_sections.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let ((new_index, new_leafs), new_subsecs) = rayon::join(
+            || {
+                rayon::join(
+                    || f_index(path, index),
+                    || {
+                        pages
+                            .into_par_iter()
+                            .map(|(slug, page)| {
+                                let new_leaf = f_leaf(path, &slug, page);
+                                (slug, new_leaf)
+                            })
+                            .collect::<Vec<_>>()
+                    },
+                )
+            },
+            || {
+                sub_sections
+                    .into_par_iter()
+                    .map(|(slug, sec)| {
+                        let mut path = path.to_vec();
+                        path.push(slug.clone());
+                        let new_sec = sec.try_par_walk_helper(&path, f_index, f_leaf);
+                        (slug, new_sec)
+                    })
+                    .collect::<Vec<_>>()
+            },
+        );
+
+        // All pages have been processed at this point, so report the first error in a fixed order
+        // (index, pages, sub-sections; each ordered by slug) instead of whichever failed first.
+        let new_index = new_index?;
+        let new_leafs = new_leafs
+            .into_iter()
+            .map(|(slug, res)| Ok((slug, res?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        let new_subsecs = new_subsecs
+            .into_iter()
+            .map(|(slug, res)| Ok((slug, res?)))
             .collect::<Result<HashMap<_, _>>>()?;
-
-        let new_subsecs = if self.sub_sections.is_empty() {
-            HashMap::new()
-        } else {
-            self.sub_sections
-                .drain()
-                .map(|(slug, sec)| {
-                    // TODO how to get rid of the excessive cloneing?
-                    let mut path = path.clone();
-                    path.push(slug.clone());
-                    Ok((
-                        slug,
-                        sec.try_walk_helper(path, f_index.clone(), f_leaf.clone())?,
-                    ))
-                })
-                .collect::<Result<HashMap<_, _>>>()?
-        };
         Ok(Section {
-            slug: self.slug,
+            slug,
             index: new_index,
             pages: new_leafs,
             sub_sections: new_subsecs,
@@ -119,16 +144,54 @@ impl<I, L> Section<I, L> {
     /// Here the context is given by
     /// - the fully qualified section path (i.e. the sequence of [`Slug`]s) of this [`Section`] for this [`Section`]'s index page, as well as
     /// - the fully qualified section path alongside the respective page [`Slug`] for all leaf pages contained in this [`Section`].
-    pub fn try_walk<J, M, FIndex, FLeaf>(
+    ///
+    /// Pages are processed concurrently on the current `rayon` thread pool.
+    /// Errors are reported deterministically, i.e. independent of the order in which pages finish.
+    pub fn try_par_walk<J, M, FIndex, FLeaf>(
         self,
         f_index: FIndex,
         f_leaf: FLeaf,
     ) -> Result<Section<J, M>>
     where
-        FIndex: Fn(&[Slug], I) -> Result<J> + Clone, // Clone to prevent recursive type
-        FLeaf: Fn(&[Slug], &Slug, L) -> Result<M> + Clone,
+        I: Send,
+        L: Send,
+        J: Send,
+        M: Send,
+        FIndex: Fn(&[Slug], I) -> Result<J> + Sync,
+        FLeaf: Fn(&[Slug], &Slug, L) -> Result<M> + Sync,
+    {
+        self.try_par_walk_helper(&[], &f_index, &f_leaf)
+    }
+
+    fn for_each_helper<FIndex, FLeaf>(
+        &self,
+        path: &mut Vec<Slug>,
+        f_index: &mut FIndex,
+        f_leaf: &mut FLeaf,
+    ) where
+        FIndex: FnMut(&[Slug], &I),
+        FLeaf: FnMut(&[Slug], &Slug, &L),
+    {
+        f_index(path, &self.index);
+        for (slug, page) in &self.pages {
+            f_leaf(path, slug, page);
+        }
+        for (slug, sec) in &self.sub_sections {
+            path.push(slug.clone());
+            sec.for_each_helper(path, f_index, f_leaf);
+            path.pop();
+        }
+    }
+
+    /// Visit all pages of this [`Section`] by reference.
+    ///
+    /// The functions get access to the same context as in [`Section::try_par_walk`].
+    pub fn for_each<FIndex, FLeaf>(&self, f_index: &mut FIndex, f_leaf: &mut FLeaf)
+    where
+        FIndex: FnMut(&[Slug], &I),
+        FLeaf: FnMut(&[Slug], &Slug, &L),
     {
-        self.try_walk_helper(Vec::new(), f_index, f_leaf)
+        self.for_each_helper(&mut Vec::new(), f_index, f_leaf);
     }
 
     pub fn slug(&self) -> &Slug {
@@ -149,11 +212,16 @@ where
             sub_sections,
         } = self;
 
+        // Sort by slug so that the resulting dictionaries do not depend on `HashMap` ordering
+        let mut pages: Vec<_> = pages.iter().collect();
+        pages.sort_by_key(|(slug, _)| *slug);
         let mut pages_typst = Dict::new();
         for (slug, page) in pages {
             pages_typst.insert(slug.as_str().into(), page.into_value());
         }
 
+        let mut sub_sections: Vec<_> = sub_sections.iter().collect();
+        sub_sections.sort_by_key(|(slug, _)| *slug);
         let mut sub_sections_typst = Dict::new();
         for (slug, sec) in sub_sections {
             sub_sections_typst.insert(slug.as_str().into(), Value::Dict(sec.to_typst()));
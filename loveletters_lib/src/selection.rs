@@ -0,0 +1,221 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Split `path` into its `/`-separated segments, ignoring empty segments and `.`.
+fn split(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect()
+}
+
+/// Convert `path` into `/`-separated segments.
+fn path_segments(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(segment) => Some(segment.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `text` matches `pattern`, where items of `pattern` for which `is_star` holds match any
+/// sequence of items and all other items match single items of `text` as decided by `matches`.
+///
+/// Only the most recent star is backtracked to, which bounds the matching effort by the product of
+/// both lengths instead of being exponential in the number of stars.
+fn matches_wildcard<P, T>(
+    pattern: &[P],
+    text: &[T],
+    is_star: impl Fn(&P) -> bool,
+    matches: impl Fn(&P, &T) -> bool,
+) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Position of the most recent star in `pattern` and of the text it currently extends to
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && is_star(&pattern[p]) {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && matches(&pattern[p], &text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((star, star_t)) = backtrack {
+            // Let the star swallow one more item
+            backtrack = Some((star, star_t + 1));
+            p = star + 1;
+            t = star_t + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(is_star)
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any sequence and `?` any single character.
+fn matches_segment(pattern: &[char], text: &[char]) -> bool {
+    matches_wildcard(pattern, text, |c| *c == '*', |c, t| *c == '?' || c == t)
+}
+
+/// A glob pattern over `/`-separated paths.
+///
+/// Within a segment, `*` matches any sequence and `?` any single character. A `**` segment
+/// matches any number of segments.
+struct Glob(Vec<Vec<char>>);
+
+impl Glob {
+    fn new(pattern: &str) -> Self {
+        Self(
+            split(pattern)
+                .into_iter()
+                .map(|segment| segment.chars().collect())
+                .collect(),
+        )
+    }
+
+    fn matches(&self, path: &[String]) -> bool {
+        let path: Vec<Vec<char>> = path
+            .iter()
+            .map(|segment| segment.chars().collect())
+            .collect();
+        matches_wildcard(
+            &self.0,
+            &path,
+            |segment| segment.as_slice() == ['*', '*'],
+            |segment, text| matches_segment(segment, text),
+        )
+    }
+}
+
+/// The pages selected for rendering.
+///
+/// Pages are selected by glob patterns over their content path (relative to either the project
+/// directory or its content directory) or their URL path.
+pub struct Selection {
+    project_dir: PathBuf,
+    content_dir: PathBuf,
+    /// No patterns select all pages.
+    patterns: Vec<Glob>,
+}
+
+impl Selection {
+    pub fn new(project_dir: PathBuf, content_dir: PathBuf, patterns: &[String]) -> Self {
+        Self {
+            project_dir,
+            content_dir,
+            patterns: patterns.iter().map(|pattern| Glob::new(pattern)).collect(),
+        }
+    }
+
+    /// Whether the page at `page_dir` with root file `typst_file`, served at `url`, is selected.
+    pub fn contains(&self, page_dir: &Path, typst_file: &Path, url: &str) -> bool {
+        if self.patterns.is_empty() {
+            return true;
+        }
+
+        let mut candidates = vec![split(url).into_iter().map(str::to_owned).collect()];
+        for path in [page_dir, typst_file] {
+            for base in [&self.project_dir, &self.content_dir] {
+                if let Ok(relative) = path.strip_prefix(base) {
+                    candidates.push(path_segments(relative));
+                }
+            }
+        }
+
+        self.patterns.iter().any(|pattern| {
+            candidates
+                .iter()
+                .any(|candidate| pattern.matches(candidate))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Glob, Selection};
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        let path: Vec<_> = path.split('/').map(str::to_owned).collect();
+        Glob::new(pattern).matches(&path)
+    }
+
+    #[test]
+    fn star_matches_within_segment() {
+        assert!(matches("posts/*", "posts/hello"));
+        assert!(matches("posts/h*o", "posts/hello"));
+        assert!(matches("posts/*", "posts/"));
+        assert!(!matches("posts/*", "posts/hello/page.typ"));
+        assert!(!matches("posts/h*x", "posts/hello"));
+    }
+
+    #[test]
+    fn question_mark_matches_single_character() {
+        assert!(matches("posts/hell?", "posts/hello"));
+        assert!(!matches("posts/hell?", "posts/hell"));
+        assert!(!matches("posts/h?", "posts/hello"));
+    }
+
+    #[test]
+    fn double_star_matches_any_number_of_segments() {
+        assert!(matches("**", "posts/hello/page.typ"));
+        assert!(matches("posts/**", "posts"));
+        assert!(matches("posts/**/page.typ", "posts/page.typ"));
+        assert!(matches("posts/**/page.typ", "posts/2025/hello/page.typ"));
+        assert!(!matches("posts/**/page.typ", "about/page.typ"));
+        assert!(!matches("**/index.typ", "posts/hello/page.typ"));
+    }
+
+    #[test]
+    fn many_stars_match_in_reasonable_time() {
+        let text = "a".repeat(200);
+        assert!(!matches(&format!("{}b", "a*".repeat(20)), &text));
+        let path = ["a"; 200].join("/");
+        assert!(!matches(&format!("{}b", "**/a/".repeat(20)), &path));
+    }
+
+    fn selection(patterns: &[&str]) -> Selection {
+        let patterns: Vec<_> = patterns.iter().map(ToString::to_string).collect();
+        Selection::new("/project".into(), "/project/content".into(), &patterns)
+    }
+
+    fn contains(selection: &Selection, page_dir: &str, url: &str) -> bool {
+        let page_dir = Path::new(page_dir);
+        selection.contains(page_dir, &page_dir.join("page.typ"), url)
+    }
+
+    #[test]
+    fn selects_everything_without_patterns() {
+        assert!(contains(
+            &selection(&[]),
+            "/project/content/about",
+            "/about/"
+        ));
+    }
+
+    #[test]
+    fn selects_by_url() {
+        let selection = selection(&["/posts/*/"]);
+        assert!(contains(
+            &selection,
+            "/project/content/posts/hello",
+            "/posts/hello/"
+        ));
+        assert!(!contains(&selection, "/project/content/about", "/about/"));
+    }
+
+    #[test]
+    fn selects_by_content_path() {
+        // Relative to the content directory
+        let selection = selection(&["posts/hello"]);
+        assert!(contains(&selection, "/project/content/posts/hello", "/p/"));
+        assert!(!contains(&selection, "/project/content/posts/world", "/p/"));
+        // Relative to the project directory, including the typst file
+        let selection = self::selection(&["content/**/page.typ"]);
+        assert!(contains(&selection, "/project/content/posts/hello", "/p/"));
+        assert!(!contains(
+            &selection,
+            "/elsewhere/content/posts/hello",
+            "/p/"
+        ));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::error::{Error, Result};
 use std::{ffi::OsStr, path::Path};
 
-#[derive(Hash, PartialEq, Eq, Debug, Clone)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
 pub struct Slug(String);
 
 impl Slug {
@@ -1,14 +1,276 @@
 use crate::error::{Error, Result};
-use std::{fs, io::ErrorKind, path::Path};
+use std::{
+    env::var,
+    ffi::OsString,
+    fs,
+    io::ErrorKind,
+    path::{self, Path, PathBuf},
+    process,
+};
+use time::{OffsetDateTime, UtcOffset};
+
+#[cfg(test)]
+use std::env::temp_dir;
+
+/// Environment variable fixing the build timestamp, see <https://reproducible-builds.org/specs/source-date-epoch/>.
+const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
 
 pub fn ensure_exists(path: &Path) -> Result<()> {
-    match fs::create_dir(path) {
-        Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(()),
-        r => r,
-    }
-    .map_err(|e| Error::FileIO {
+    // `create_dir_all` also copes with parents being created concurrently
+    fs::create_dir_all(path).map_err(|e| Error::FileIO {
         path: Some(path.to_path_buf()),
         raw: e,
     })?;
     Ok(())
 }
+
+/// Hidden sibling of `path` to prepare new content for `path` in.
+///
+/// Once complete, the sibling is moved into place. This way, readers of `path` never see partial
+/// content, and an interruption leaves nothing but the sibling behind. The sibling is specific to
+/// this process, so that concurrent builds do not interfere.
+pub fn temporary_sibling(path: &Path) -> PathBuf {
+    let mut temp_name = OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(".{}.tmp", process::id()));
+    path.with_file_name(temp_name)
+}
+
+/// Write `content` to `path` such that readers either see the previous or the new content.
+///
+/// The content is written to a [`temporary_sibling`] first.
+pub fn write_atomically(path: &Path, content: &[u8]) -> Result<()> {
+    let temp_path = temporary_sibling(path);
+
+    fs::write(&temp_path, content)
+        .and_then(|()| fs::rename(&temp_path, path))
+        .map_err(|e| {
+            // The temporary file is of no use anymore, no matter whether it got written
+            let _ = fs::remove_file(&temp_path);
+            Error::FileIO {
+                path: Some(path.to_path_buf()),
+                raw: e,
+            }
+        })
+}
+
+/// Resolve `path` to an absolute path without requiring it to exist.
+///
+/// The longest existing prefix of `path` is canonicalized, the remainder is appended as is.
+fn try_resolve(path: &Path) -> Result<PathBuf> {
+    let path = path::absolute(path).map_err(|e| Error::FileIO {
+        path: Some(path.to_path_buf()),
+        raw: e,
+    })?;
+    let mut existing = path.as_path();
+    let mut remainder = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(resolved) => {
+                return Ok(remainder
+                    .into_iter()
+                    .rev()
+                    .fold(resolved, |resolved, component| resolved.join(component)));
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                    return Err(Error::FileIO {
+                        path: Some(path.clone()),
+                        raw: e,
+                    });
+                };
+                remainder.push(name);
+                existing = parent;
+            }
+            Err(e) => {
+                return Err(Error::FileIO {
+                    path: Some(existing.to_path_buf()),
+                    raw: e,
+                });
+            }
+        }
+    }
+}
+
+/// Create the output directory `output_dir` for the project at `project_dir`.
+///
+/// Refuses output directories that would overwrite the project's content or the project itself.
+pub fn try_prepare_output_dir(
+    project_dir: &Path,
+    content_dir: &Path,
+    output_dir: &Path,
+) -> Result<PathBuf> {
+    let resolved = try_resolve(output_dir)?;
+
+    if resolved.starts_with(content_dir) {
+        return Err(Error::InvalidOutputDirectory {
+            path: output_dir.to_path_buf(),
+            reason: "it is located inside the content directory",
+        });
+    }
+    if project_dir.starts_with(&resolved) {
+        return Err(Error::InvalidOutputDirectory {
+            path: output_dir.to_path_buf(),
+            reason: "it contains the project directory",
+        });
+    }
+
+    fs::create_dir_all(&resolved).map_err(|e| Error::FileIO {
+        path: Some(resolved.clone()),
+        raw: e,
+    })?;
+    Ok(resolved)
+}
+
+/// Resolve the typst root `typst_root` of the project with content directory `content_dir`.
+///
+/// Every page's files have to be located inside the typst root, so the typst root has to contain
+/// the content directory.
+pub fn try_resolve_typst_root(content_dir: &Path, typst_root: &Path) -> Result<PathBuf> {
+    let resolved = typst_root.canonicalize().map_err(|e| match e.kind() {
+        ErrorKind::NotFound => Error::InvalidTypstRoot {
+            path: typst_root.to_path_buf(),
+            reason: "it does not exist",
+        },
+        _ => Error::FileIO {
+            path: Some(typst_root.to_path_buf()),
+            raw: e,
+        },
+    })?;
+
+    if !content_dir.starts_with(&resolved) {
+        return Err(Error::InvalidTypstRoot {
+            path: typst_root.to_path_buf(),
+            reason: "it does not contain the content directory",
+        });
+    }
+    Ok(resolved)
+}
+
+/// Determine the build's timestamp.
+///
+/// `build_date` takes precedence over the `SOURCE_DATE_EPOCH` environment variable, which in turn
+/// takes precedence over the current time. The timestamp is returned in UTC.
+pub fn try_build_timestamp(build_date: Option<OffsetDateTime>) -> Result<OffsetDateTime> {
+    if let Some(build_date) = build_date {
+        return Ok(build_date.to_offset(UtcOffset::UTC));
+    }
+    let Ok(epoch) = var(SOURCE_DATE_EPOCH) else {
+        return Ok(OffsetDateTime::now_utc());
+    };
+    epoch
+        .trim()
+        .parse::<i64>()
+        .map_err(anyhow::Error::from)
+        .and_then(|seconds| Ok(OffsetDateTime::from_unix_timestamp(seconds)?))
+        .map_err(|e| Error::InvalidBuildDate {
+            value: epoch.clone(),
+            raw: e.context(format!("{SOURCE_DATE_EPOCH} must be a number of seconds")),
+        })
+}
+
+/// Empty directory for a single test, removed again when dropped.
+#[cfg(test)]
+pub struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    /// Create an empty directory for the test `name`, canonicalized for comparisons.
+    pub fn new(name: &str) -> Self {
+        let path = temp_dir().join(format!("loveletters-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("test directory should be creatable");
+        Self(path.canonicalize().expect("test directory should exist"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{TestDir, try_prepare_output_dir, try_resolve};
+    use crate::error::Error;
+
+    /// A project with a content directory inside `dir`, returning both directories.
+    fn project(dir: &TestDir) -> (PathBuf, PathBuf) {
+        let project_dir = dir.path().join("project");
+        let content_dir = project_dir.join("content");
+        fs::create_dir_all(&content_dir).unwrap();
+        (project_dir, content_dir)
+    }
+
+    #[test]
+    fn resolves_missing_paths() {
+        let dir = TestDir::new("resolve-missing");
+        let missing = dir.path().join("missing").join("nested");
+        assert_eq!(try_resolve(&missing).unwrap(), missing);
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn resolves_existing_prefix() {
+        let dir = TestDir::new("resolve-prefix");
+        fs::create_dir(dir.path().join("existing")).unwrap();
+        let path = dir.path().join("existing").join(".").join("missing");
+        assert_eq!(
+            try_resolve(&path).unwrap(),
+            dir.path().join("existing").join("missing")
+        );
+    }
+
+    #[test]
+    fn creates_missing_output_dir() {
+        let dir = TestDir::new("output-missing");
+        let (project_dir, content_dir) = project(&dir);
+        let output_dir = project_dir.join("public").join("nested");
+        let resolved = try_prepare_output_dir(&project_dir, &content_dir, &output_dir).unwrap();
+        assert_eq!(resolved, output_dir);
+        assert!(output_dir.is_dir());
+    }
+
+    #[test]
+    fn rejects_output_dir_inside_content() {
+        let dir = TestDir::new("output-content");
+        let (project_dir, content_dir) = project(&dir);
+        for output_dir in [content_dir.clone(), content_dir.join("public")] {
+            assert!(matches!(
+                try_prepare_output_dir(&project_dir, &content_dir, &output_dir),
+                Err(Error::InvalidOutputDirectory { .. })
+            ));
+        }
+        assert!(!content_dir.join("public").exists());
+    }
+
+    #[test]
+    fn rejects_project_dir() {
+        let dir = TestDir::new("output-project");
+        let (project_dir, content_dir) = project(&dir);
+        assert!(matches!(
+            try_prepare_output_dir(&project_dir, &content_dir, &project_dir),
+            Err(Error::InvalidOutputDirectory { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_parent_of_project_dir() {
+        let dir = TestDir::new("output-parent");
+        let (project_dir, content_dir) = project(&dir);
+        for output_dir in [dir.path().to_path_buf(), project_dir.join("..")] {
+            assert!(matches!(
+                try_prepare_output_dir(&project_dir, &content_dir, &output_dir),
+                Err(Error::InvalidOutputDirectory { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    error,
+    fmt::{self, Debug, Display},
+    ops::Range,
+    path::Path,
+};
+
+use serde::Deserialize;
+use toml::de::{self, DeTable, DeValue};
+
+/// Keys accepted at some position of a TOML document.
+pub enum Keys {
+    /// Accept any value without further checks.
+    Unchecked,
+    /// Accept a table with exactly the given keys.
+    Table(&'static [(&'static str, Keys)]),
+}
+
+/// Types deserialized from TOML documents that know which keys they accept.
+pub trait KnownKeys {
+    const KNOWN_KEYS: Keys;
+}
+
+/// How to treat unknown keys in configuration files and frontmatter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Validation {
+    /// Warn about unknown keys.
+    #[default]
+    Lenient,
+    /// Fail on unknown keys.
+    Strict,
+}
+
+impl Validation {
+    /// Deserialize `source`, checking it for unknown keys and acting according to this
+    /// [`Validation`] mode.
+    ///
+    /// Unknown keys are looked for before deserializing, so that they are reported even if they
+    /// make deserialization fail (e.g. a misspelt required key). In [`Validation::Lenient`] mode,
+    /// unknown keys of otherwise valid documents are reported on stderr, attributed to `location`.
+    pub fn try_parse<T: for<'de> Deserialize<'de> + KnownKeys>(
+        self,
+        source: &str,
+        location: &Path,
+    ) -> anyhow::Result<T> {
+        let mut unknown = UnknownKeys::find(source, &T::KNOWN_KEYS);
+        let parsed = match toml::from_str(source) {
+            Ok(parsed) => parsed,
+            Err(e) if unknown.keys.is_empty() => return Err(e.into()),
+            // The unknown keys likely explain why deserialization failed
+            Err(e) => {
+                unknown.parse_error = Some(e);
+                return Err(unknown.into());
+            }
+        };
+        if unknown.keys.is_empty() {
+            return Ok(parsed);
+        }
+        match self {
+            Validation::Strict => Err(unknown.into()),
+            Validation::Lenient => {
+                for key in unknown.keys {
+                    eprintln!("warning: {}: {key}", location.display());
+                }
+                Ok(parsed)
+            }
+        }
+    }
+}
+
+pub struct UnknownKey {
+    key: String,
+    line: usize,
+    column: usize,
+    suggestion: Option<&'static str>,
+}
+
+impl UnknownKey {
+    fn new(
+        source: &str,
+        span: &Range<usize>,
+        key: String,
+        suggestion: Option<&'static str>,
+    ) -> Self {
+        let before = &source[..span.start];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rfind('\n')
+            .map_or(before, |newline| &before[newline + 1..])
+            .chars()
+            .count()
+            + 1;
+        Self {
+            key,
+            line,
+            column,
+            suggestion,
+        }
+    }
+}
+
+impl Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown key '{}' at line {}, column {}",
+            self.key, self.line, self.column
+        )?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, ", did you mean '{suggestion}'?")?;
+        }
+        Ok(())
+    }
+}
+
+/// Unknown keys encountered in a TOML document.
+pub struct UnknownKeys {
+    keys: Vec<UnknownKey>,
+    /// Why the document failed to deserialize, if it did.
+    parse_error: Option<de::Error>,
+}
+
+impl UnknownKeys {
+    fn find(source: &str, keys: &Keys) -> Self {
+        // Syntax errors are reported when actually deserializing the document
+        let Ok(table) = DeTable::parse(source) else {
+            return Self {
+                keys: Vec::new(),
+                parse_error: None,
+            };
+        };
+        let mut spans = Vec::new();
+        collect_unknown(table.get_ref(), keys, "", &mut spans);
+        spans.sort_by_key(|(span, _, _)| span.start);
+        Self {
+            keys: spans
+                .into_iter()
+                .map(|(span, key, suggestion)| UnknownKey::new(source, &span, key, suggestion))
+                .collect(),
+            parse_error: None,
+        }
+    }
+}
+
+type UnknownSpan = (Range<usize>, String, Option<&'static str>);
+
+fn collect_unknown(table: &DeTable<'_>, keys: &Keys, prefix: &str, unknown: &mut Vec<UnknownSpan>) {
+    let Keys::Table(known) = keys else {
+        return;
+    };
+    for (key, value) in table {
+        let name: &str = key.get_ref();
+        if let Some((_, nested)) = known.iter().find(|(k, _)| *k == name) {
+            if let DeValue::Table(nested_table) = value.get_ref() {
+                collect_unknown(nested_table, nested, &format!("{prefix}{name}."), unknown);
+            }
+        } else {
+            unknown.push((key.span(), format!("{prefix}{name}"), suggest(name, known)));
+        }
+    }
+}
+
+/// Find the known key closest to `key`, if any is close enough to likely be meant instead.
+fn suggest(key: &str, known: &[(&'static str, Keys)]) -> Option<&'static str> {
+    known
+        .iter()
+        .map(|(k, _)| (*k, strsim::levenshtein(key, k)))
+        .filter(|(k, distance)| *distance <= 3 && *distance < k.len())
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(k, _)| k)
+}
+
+impl Display for UnknownKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "encountered {} unknown key(s)", self.keys.len())
+    }
+}
+
+impl Debug for UnknownKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for key in &self.keys {
+            write!(f, "\n    {key}")?;
+        }
+        if let Some(parse_error) = &self.parse_error {
+            write!(f, "\n\n{parse_error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for UnknownKeys {}
+
+#[cfg(test)]
+mod tests {
+    use std::{error, fmt};
+
+    use serde::{
+        Deserialize,
+        de::{self, Visitor},
+    };
+
+    use std::path::Path;
+
+    use super::{Keys, KnownKeys, UnknownKey, UnknownKeys, Validation, suggest};
+    use crate::{
+        config::{Config, FontConfig, PackageConfig, Requirements, TypstConfig},
+        content::{IndexFrontmatter, LeafFrontmatter},
+    };
+
+    /// Deserializer that merely records which fields a struct expects.
+    struct Fields;
+
+    #[derive(Debug)]
+    struct Expected(Option<&'static [&'static str]>);
+
+    impl fmt::Display for Expected {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+
+    impl error::Error for Expected {}
+
+    impl de::Error for Expected {
+        fn custom<T: fmt::Display>(_: T) -> Self {
+            Self(None)
+        }
+    }
+
+    impl<'de> de::Deserializer<'de> for Fields {
+        type Error = Expected;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(Expected(None))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            Err(Expected(Some(fields)))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+            ignored_any
+        }
+    }
+
+    /// Assert that the known keys of `T` are exactly the fields serde deserializes.
+    fn assert_keys_match_fields<T: for<'de> Deserialize<'de> + KnownKeys>() {
+        let Err(Expected(Some(fields))) = T::deserialize(Fields) else {
+            panic!("not deserialized as a struct");
+        };
+        let Keys::Table(known) = T::KNOWN_KEYS else {
+            panic!("not checked as a table");
+        };
+        let mut fields = fields.to_vec();
+        let mut known: Vec<_> = known.iter().map(|(k, _)| *k).collect();
+        fields.sort_unstable();
+        known.sort_unstable();
+        assert_eq!(known, fields);
+    }
+
+    #[test]
+    fn known_keys_match_fields() {
+        assert_keys_match_fields::<Config>();
+        assert_keys_match_fields::<FontConfig>();
+        assert_keys_match_fields::<PackageConfig>();
+        assert_keys_match_fields::<TypstConfig>();
+        assert_keys_match_fields::<Requirements>();
+        assert_keys_match_fields::<IndexFrontmatter>();
+        assert_keys_match_fields::<LeafFrontmatter>();
+    }
+
+    const KNOWN: &[(&str, Keys)] = &[
+        ("title", Keys::Unchecked),
+        ("publication", Keys::Unchecked),
+        ("id", Keys::Unchecked),
+    ];
+
+    #[test]
+    fn suggests_closest_key() {
+        assert_eq!(suggest("titel", KNOWN), Some("title"));
+        assert_eq!(suggest("publicaton", KNOWN), Some("publication"));
+        assert_eq!(suggest("Title", KNOWN), Some("title"));
+    }
+
+    #[test]
+    fn suggests_nothing_for_distant_keys() {
+        assert_eq!(suggest("description", KNOWN), None);
+        // Replacing every character of a short key is not a typo
+        assert_eq!(suggest("xy", KNOWN), None);
+    }
+
+    #[test]
+    fn locates_key_on_first_line() {
+        let source = "titel = 'Hello'";
+        let key = UnknownKey::new(source, &(0..5), "titel".to_owned(), None);
+        assert_eq!((key.line, key.column), (1, 1));
+    }
+
+    #[test]
+    fn locates_key_on_later_line() {
+        let source = "title = 'Hello'\n\n  publicaton = 2025-01-01";
+        let start = source.find("publicaton").unwrap();
+        let key = UnknownKey::new(source, &(start..start + 10), "publicaton".to_owned(), None);
+        assert_eq!((key.line, key.column), (3, 3));
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        let source = "title = 'Grüße'\n[x]\näöü = 1\nkey = 1";
+        let start = source.rfind("key").unwrap();
+        let key = UnknownKey::new(source, &(start..start + 3), "key".to_owned(), None);
+        assert_eq!((key.line, key.column), (4, 1));
+        let source = "title = 'Grüße'; key = 1";
+        let start = source.find("key").unwrap();
+        let key = UnknownKey::new(source, &(start..start + 3), "key".to_owned(), None);
+        assert_eq!((key.line, key.column), (1, 18));
+    }
+
+    #[test]
+    fn finds_nested_unknown_keys() {
+        const NESTED: Keys = Keys::Table(&[
+            ("title", Keys::Unchecked),
+            ("fonts", Keys::Table(&[("paths", Keys::Unchecked)])),
+        ]);
+        let unknown = UnknownKeys::find("titel = 'x'\n[fonts]\npath = []\n", &NESTED);
+        let keys: Vec<_> = unknown
+            .keys
+            .iter()
+            .map(|k| (k.key.as_str(), k.line, k.suggestion))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("titel", 1, Some("title")),
+                ("fonts.path", 3, Some("paths"))
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unknown_keys_that_break_deserialization() {
+        let source = "titel = 'Hello'\npublication = 2025-01-01T00:00:00Z\n";
+        let err = Validation::Lenient
+            .try_parse::<LeafFrontmatter>(source, Path::new("frontmatter.toml"))
+            .unwrap_err();
+        let unknown = err.downcast_ref::<UnknownKeys>().unwrap();
+        assert_eq!(unknown.keys[0].suggestion, Some("title"));
+        assert!(unknown.parse_error.is_some());
+    }
+}
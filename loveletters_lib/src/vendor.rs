@@ -0,0 +1,88 @@
+//! Vendoring packages into a project.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use crate::{
+    error::{Error, Result},
+    report::ResolvedPackage,
+    utils::{ensure_exists, temporary_sibling},
+};
+
+/// Copy all files below `source` to `target`, creating `target` along the way.
+fn try_copy_dir(source: &Path, target: &Path) -> Result<()> {
+    for entry in WalkDir::new(source) {
+        let entry = entry.map_err(|e| Error::FileIO {
+            path: e.path().map(Path::to_path_buf),
+            raw: e.into(),
+        })?;
+        let Ok(relative) = entry.path().strip_prefix(source) else {
+            continue;
+        };
+        let destination = target.join(relative);
+        if entry.file_type().is_dir() {
+            ensure_exists(&destination)?;
+        } else {
+            fs::copy(entry.path(), &destination).map_err(|e| Error::FileIO {
+                path: Some(destination.clone()),
+                raw: e,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy `package` to `target`, replacing a previous copy.
+///
+/// The package is copied to a [`temporary_sibling`] first.
+fn try_vendor_package(package: &ResolvedPackage, target: &Path) -> Result<()> {
+    let temp_dir = temporary_sibling(target);
+
+    let copied = try_copy_dir(&package.path, &temp_dir).and_then(|()| {
+        if target.exists() {
+            fs::remove_dir_all(target).map_err(|e| Error::FileIO {
+                path: Some(target.to_path_buf()),
+                raw: e,
+            })?;
+        }
+        fs::rename(&temp_dir, target).map_err(|e| Error::FileIO {
+            path: Some(target.to_path_buf()),
+            raw: e,
+        })
+    });
+    if copied.is_err() {
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+    copied
+}
+
+/// Copy `packages` into `packages_dir`, at `<namespace>/<name>/<version>`.
+///
+/// Returns the vendored packages, pointing to their new location. Packages already located in
+/// `packages_dir` are left as is.
+pub(crate) fn try_vendor(
+    packages_dir: &Path,
+    packages: Vec<ResolvedPackage>,
+) -> Result<Vec<ResolvedPackage>> {
+    packages
+        .into_iter()
+        .map(|package| {
+            let target: PathBuf = [&package.namespace, &package.name, &package.version]
+                .iter()
+                .fold(packages_dir.to_path_buf(), |dir, segment| dir.join(segment));
+            if package.path.starts_with(packages_dir) {
+                return Ok(package);
+            }
+            ensure_exists(target.parent().unwrap_or(packages_dir))?;
+            try_vendor_package(&package, &target)?;
+            Ok(ResolvedPackage {
+                path: target,
+                ..package
+            })
+        })
+        .collect()
+}
@@ -12,6 +12,11 @@
   let parent = section.fold((loveletters.project.content, ()), folder)
   let path = parent.at(1)
 
+  let language = loveletters.project.language
+  if language != none {
+    path.insert(0, language)
+  }
+
   if page != none {
     path.push(page)
   }